@@ -1,9 +1,22 @@
+// Explicit `return`s and `match` arms that fall through with `()` are the
+// style this lexer was written in, so these two lints are off for it.
+#![allow(clippy::needless_return, clippy::single_match)]
 use regex::bytes::Regex;
-#[derive(Debug)]
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::{ControlFlow, Deref, Range};
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     value: TokenValue,
+    span: Range<usize>,
+    source_id: u32,
     line: usize,
     column: usize,
+    // Where the next token would start, counted like `line` and `column`.
+    end_line: usize,
+    end_column: usize,
 }
 
 #[derive(Debug)]
@@ -11,17 +24,27 @@ pub struct LexerError {
     value: LexerErrorValue,
     line: usize,
     column: usize,
+    suggestion: Option<&'static str>,
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum LexerErrorValue {
-    UnrecognizedToken
+    UnrecognizedToken,
+    TooManyTokens,
+    LeadingZero,
+    NumberOverflow,
+    StrayBackslash,
+    UnterminatedComment,
+    NumberTooLong,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum TokenValue {
     Number(i32),
-    Boolean(bool),
+    Number64(i64),
+    Boolean(BooleanRepr),
     Character(u8),
     Cross,
     Dash,
@@ -38,18 +61,777 @@ pub enum TokenValue {
     DoubleAnd,
     DoublePipe,
     Excl,
+    Identifier(String),
+    Symbol(u32),
+    Underscore,
+    Eof,
+    Unknown(u8),
+    At,
+    Hash,
+    Semicolon,
+    PipeForward,
+    Tilde,
+}
+
+// A boolean literal together with the spelling it was lexed from, so
+// `true` displays as `true` under `BoolStyle::Both`. Two reprs are only
+// equal when their spellings match too; compare `value()` to ignore that.
+// Table spellings are borrowed; only a case-insensitive match such as
+// `TRUE` has to own its spelling.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BooleanRepr {
+    value: bool,
+    spelling: Cow<'static, str>,
+}
+
+impl BooleanRepr {
+    pub const fn new(value: bool, spelling: &'static str) -> BooleanRepr {
+        BooleanRepr { value, spelling: Cow::Borrowed(spelling) }
+    }
+
+    pub fn value(&self) -> bool {
+        return self.value;
+    }
+
+    pub fn spelling(&self) -> &str {
+        return &self.spelling;
+    }
+}
+
+// Uses the default `BoolStyle::Capitalized` spelling.
+impl From<bool> for BooleanRepr {
+    fn from(value: bool) -> Self {
+        BooleanRepr::new(value, if value { "True" } else { "False" })
+    }
+}
+
+// `TokenValue` without its payload, for matching on what a token is rather
+// than what it holds. Declared in the same order as `TokenValue`, which is
+// also the `Ord` order, so sorted tables keyed on it can be binary-searched.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum TokenKind {
+    Number,
+    Number64,
+    Boolean,
+    Character,
+    Cross,
+    Dash,
+    Star,
+    Slash,
+    Whitespace,
+    OpenRoundBracket,
+    CloseRoundBracket,
+    OpenCurlyBracket,
+    CloseCurlyBracket,
+    Equal,
+    ExclEqual,
+    DoubleEqual,
+    DoubleAnd,
+    DoublePipe,
+    Excl,
+    Identifier,
+    Symbol,
+    Underscore,
+    Eof,
+    Unknown,
+    At,
+    Hash,
+    Semicolon,
+    PipeForward,
+    Tilde,
+}
+
+// Ordered by declaration, so keep new variants at the end to avoid
+// reshuffling any table sorted on this.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum TokenCategory {
+    Keyword,
+    Literal,
+    Operator,
+    Punctuation,
+    Identifier,
+    Whitespace,
+    Unknown,
+}
+
+// Coarser than `TokenCategory` and split the way editors colour code.
+// `Comment` and `Error` cover source that never became a token.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum HighlightCategory {
+    Number,
+    Boolean,
+    Operator,
+    Bracket,
+    Whitespace,
+    Comment,
+    Identifier,
+    Error,
+}
+
+pub fn highlight_category(tv: &TokenValue) -> HighlightCategory {
+    match tv {
+        TokenValue::Number(_)
+        | TokenValue::Number64(_)
+        | TokenValue::Character(_) => HighlightCategory::Number,
+        TokenValue::Boolean(_) => HighlightCategory::Boolean,
+        TokenValue::Cross
+        | TokenValue::Dash
+        | TokenValue::Star
+        | TokenValue::Slash
+        | TokenValue::Equal
+        | TokenValue::ExclEqual
+        | TokenValue::DoubleEqual
+        | TokenValue::DoubleAnd
+        | TokenValue::DoublePipe
+        | TokenValue::PipeForward
+        | TokenValue::Tilde
+        | TokenValue::Excl
+        | TokenValue::At
+        | TokenValue::Hash
+        | TokenValue::Semicolon => HighlightCategory::Operator,
+        TokenValue::OpenRoundBracket
+        | TokenValue::CloseRoundBracket
+        | TokenValue::OpenCurlyBracket
+        | TokenValue::CloseCurlyBracket => HighlightCategory::Bracket,
+        TokenValue::Whitespace | TokenValue::Eof => HighlightCategory::Whitespace,
+        TokenValue::Identifier(_)
+        | TokenValue::Symbol(_)
+        | TokenValue::Underscore => HighlightCategory::Identifier,
+        TokenValue::Unknown(_) => HighlightCategory::Error,
+    }
+}
+
+impl TokenValue {
+    pub fn category(&self) -> TokenCategory {
+        match self {
+            TokenValue::Boolean(_) => TokenCategory::Keyword,
+            TokenValue::Number(_)
+            | TokenValue::Number64(_)
+            | TokenValue::Character(_) => TokenCategory::Literal,
+            TokenValue::Cross
+            | TokenValue::Dash
+            | TokenValue::Star
+            | TokenValue::Slash
+            | TokenValue::Equal
+            | TokenValue::ExclEqual
+            | TokenValue::DoubleEqual
+            | TokenValue::DoubleAnd
+            | TokenValue::DoublePipe
+            | TokenValue::PipeForward
+            | TokenValue::Tilde
+            | TokenValue::Excl => TokenCategory::Operator,
+            TokenValue::OpenRoundBracket
+            | TokenValue::CloseRoundBracket
+            | TokenValue::OpenCurlyBracket
+            | TokenValue::CloseCurlyBracket
+            | TokenValue::Underscore
+            | TokenValue::Eof
+            | TokenValue::At
+            | TokenValue::Hash
+            | TokenValue::Semicolon => TokenCategory::Punctuation,
+            TokenValue::Unknown(_) => TokenCategory::Unknown,
+            TokenValue::Identifier(_) | TokenValue::Symbol(_) => TokenCategory::Identifier,
+            TokenValue::Whitespace => TokenCategory::Whitespace,
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            TokenValue::Number(_) => TokenKind::Number,
+            TokenValue::Number64(_) => TokenKind::Number64,
+            TokenValue::Boolean(_) => TokenKind::Boolean,
+            TokenValue::Character(_) => TokenKind::Character,
+            TokenValue::Cross => TokenKind::Cross,
+            TokenValue::Dash => TokenKind::Dash,
+            TokenValue::Star => TokenKind::Star,
+            TokenValue::Slash => TokenKind::Slash,
+            TokenValue::Whitespace => TokenKind::Whitespace,
+            TokenValue::OpenRoundBracket => TokenKind::OpenRoundBracket,
+            TokenValue::CloseRoundBracket => TokenKind::CloseRoundBracket,
+            TokenValue::OpenCurlyBracket => TokenKind::OpenCurlyBracket,
+            TokenValue::CloseCurlyBracket => TokenKind::CloseCurlyBracket,
+            TokenValue::Equal => TokenKind::Equal,
+            TokenValue::ExclEqual => TokenKind::ExclEqual,
+            TokenValue::DoubleEqual => TokenKind::DoubleEqual,
+            TokenValue::DoubleAnd => TokenKind::DoubleAnd,
+            TokenValue::DoublePipe => TokenKind::DoublePipe,
+            TokenValue::PipeForward => TokenKind::PipeForward,
+            TokenValue::Tilde => TokenKind::Tilde,
+            TokenValue::Excl => TokenKind::Excl,
+            TokenValue::Identifier(_) => TokenKind::Identifier,
+            TokenValue::Symbol(_) => TokenKind::Symbol,
+            TokenValue::Underscore => TokenKind::Underscore,
+            TokenValue::Eof => TokenKind::Eof,
+            TokenValue::Unknown(_) => TokenKind::Unknown,
+            TokenValue::At => TokenKind::At,
+            TokenValue::Hash => TokenKind::Hash,
+            TokenValue::Semicolon => TokenKind::Semicolon,
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TokenValue::Number(_) | TokenValue::Number64(_) => "number",
+            TokenValue::Boolean(_) => "boolean literal",
+            TokenValue::Character(_) => "character",
+            TokenValue::Cross => "plus sign",
+            TokenValue::Dash => "minus sign",
+            TokenValue::Star => "asterisk",
+            TokenValue::Slash => "slash",
+            TokenValue::Whitespace => "whitespace",
+            TokenValue::OpenRoundBracket => "open parenthesis",
+            TokenValue::CloseRoundBracket => "close parenthesis",
+            TokenValue::OpenCurlyBracket => "open brace",
+            TokenValue::CloseCurlyBracket => "close brace",
+            TokenValue::Equal => "equals sign",
+            TokenValue::ExclEqual => "not-equal operator",
+            TokenValue::DoubleEqual => "equality operator",
+            TokenValue::DoubleAnd => "logical and",
+            TokenValue::DoublePipe => "logical or",
+            TokenValue::PipeForward => "pipe forward",
+            TokenValue::Tilde => "bitwise not",
+            TokenValue::Excl => "logical not",
+            TokenValue::Identifier(_) | TokenValue::Symbol(_) => "identifier",
+            TokenValue::Underscore => "placeholder",
+            TokenValue::Eof => "end of input",
+            TokenValue::Unknown(_) => "unrecognized character",
+            TokenValue::At => "at sign",
+            TokenValue::Hash => "hash sign",
+            TokenValue::Semicolon => "semicolon",
+        }
+    }
+
+    // The default spelling's length. Numbers count their decimal digits, so
+    // a `0x` literal or an operator lexed from an alias will not match its
+    // source; use `Token::span` when the exact extent matters.
+    pub fn source_len(&self) -> Option<usize> {
+        match self {
+            TokenValue::Number(n) => Some(n.to_string().len()),
+            TokenValue::Number64(n) => Some(n.to_string().len()),
+            TokenValue::Boolean(repr) => Some(repr.spelling.len()),
+            TokenValue::Cross
+            | TokenValue::Dash
+            | TokenValue::Star
+            | TokenValue::Slash
+            | TokenValue::OpenRoundBracket
+            | TokenValue::CloseRoundBracket
+            | TokenValue::OpenCurlyBracket
+            | TokenValue::CloseCurlyBracket
+            | TokenValue::Equal
+            | TokenValue::Excl
+            | TokenValue::Underscore
+            | TokenValue::Unknown(_)
+            | TokenValue::At
+            | TokenValue::Hash
+            | TokenValue::Semicolon
+            | TokenValue::Tilde => Some(1),
+            TokenValue::ExclEqual
+            | TokenValue::DoubleEqual
+            | TokenValue::DoubleAnd
+            | TokenValue::DoublePipe
+            | TokenValue::PipeForward => Some(2),
+            TokenValue::Identifier(name) => Some(name.len()),
+            TokenValue::Eof => Some(0),
+            TokenValue::Character(_)
+            | TokenValue::Whitespace
+            | TokenValue::Symbol(_) => None,
+        }
+    }
+
+    // Boolean literals are spelled as keywords (`True`/`False`), so a
+    // highlighter treats them like any future `if`/`else`.
+    pub fn is_keyword(&self) -> bool {
+        return self.category() == TokenCategory::Keyword;
+    }
+}
+
+// Renders the source spelling a token would be lexed from by default.
+// Interned symbols have no spelling without their interner, so they print
+// as `#` and their id.
+impl fmt::Display for TokenValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenValue::Number(n) => write!(f, "{}", n),
+            TokenValue::Number64(n) => write!(f, "{}", n),
+            TokenValue::Boolean(repr) => write!(f, "{}", repr.spelling),
+            TokenValue::Character(c) | TokenValue::Unknown(c) => write!(f, "{}", *c as char),
+            TokenValue::Cross => write!(f, "+"),
+            TokenValue::Dash => write!(f, "-"),
+            TokenValue::Star => write!(f, "*"),
+            TokenValue::Slash => write!(f, "/"),
+            TokenValue::Whitespace => write!(f, " "),
+            TokenValue::OpenRoundBracket => write!(f, "("),
+            TokenValue::CloseRoundBracket => write!(f, ")"),
+            TokenValue::OpenCurlyBracket => write!(f, "{{"),
+            TokenValue::CloseCurlyBracket => write!(f, "}}"),
+            TokenValue::Equal => write!(f, "="),
+            TokenValue::ExclEqual => write!(f, "!="),
+            TokenValue::DoubleEqual => write!(f, "=="),
+            TokenValue::DoubleAnd => write!(f, "&&"),
+            TokenValue::DoublePipe => write!(f, "||"),
+            TokenValue::PipeForward => write!(f, "|>"),
+            TokenValue::Tilde => write!(f, "~"),
+            TokenValue::Excl => write!(f, "!"),
+            TokenValue::Identifier(name) => write!(f, "{}", name),
+            TokenValue::Symbol(id) => write!(f, "#{}", id),
+            TokenValue::Underscore => write!(f, "_"),
+            TokenValue::Eof => Ok(()),
+            TokenValue::At => write!(f, "@"),
+            TokenValue::Hash => write!(f, "#"),
+            TokenValue::Semicolon => write!(f, ";"),
+        }
+    }
+}
+
+impl LexerError {
+    pub fn value(&self) -> &LexerErrorValue {
+        return &self.value;
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+
+    pub fn suggestion(&self) -> Option<&'static str> {
+        return self.suggestion;
+    }
+}
+
+impl Token {
+    pub fn value(&self) -> &TokenValue {
+        return &self.value;
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+
+    pub fn source_id(&self) -> u32 {
+        return self.source_id;
+    }
+
+    // Byte offsets into the lexed input, end exclusive. Comments are skipped
+    // without a token, so they fall in the gaps between spans.
+    pub fn span(&self) -> Range<usize> {
+        return self.span.clone();
+    }
+
+    pub fn category(&self) -> TokenCategory {
+        return self.value.category();
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        return self.value.kind();
+    }
+
+    pub fn is_any(&self, kinds: &[TokenKind]) -> bool {
+        return kinds.contains(&self.kind());
+    }
+
+    // `==` also compares positions; this only compares what was lexed.
+    pub fn value_eq(&self, other: &Token) -> bool {
+        return self.value == other.value;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BoolStyle {
+    #[default]
+    Capitalized,
+    Lowercase,
+    Both,
+}
+
+impl BoolStyle {
+    fn allows(&self, spelling: &str) -> bool {
+        let capitalized = spelling.starts_with(|c: char| c.is_ascii_uppercase());
+        match self {
+            BoolStyle::Capitalized => capitalized,
+            BoolStyle::Lowercase => !capitalized,
+            BoolStyle::Both => true,
+        }
+    }
+}
+
+// Words that lex as something other than an identifier. A new keyword is
+// one more entry here; boolean spellings are further filtered by `BoolStyle`.
+pub const KEYWORDS: &[(&str, TokenValue)] = &[
+    ("True", TokenValue::Boolean(BooleanRepr::new(true, "True"))),
+    ("False", TokenValue::Boolean(BooleanRepr::new(false, "False"))),
+    ("true", TokenValue::Boolean(BooleanRepr::new(true, "true"))),
+    ("false", TokenValue::Boolean(BooleanRepr::new(false, "false"))),
+];
+
+// Matches the whole word only, so `Tru` and `Truex` are not keywords.
+pub fn keyword(word: &str, bool_style: BoolStyle) -> Option<TokenValue> {
+    return lookup_keyword(word, bool_style, false);
 }
 
+// Ignoring case, `TRUE` matches the first allowed `True`/`true` entry but
+// keeps its own spelling, so it still displays as `TRUE`.
+fn lookup_keyword(word: &str, bool_style: BoolStyle, case_insensitive: bool) -> Option<TokenValue> {
+    return KEYWORDS.iter()
+        .filter(|(spelling, _)| match case_insensitive {
+            true => spelling.eq_ignore_ascii_case(word),
+            false => *spelling == word,
+        })
+        .find(|(spelling, value)| match value {
+            TokenValue::Boolean(_) => bool_style.allows(spelling),
+            _ => true,
+        })
+        .map(|(_, value)| match value {
+            TokenValue::Boolean(repr) if repr.spelling != word => TokenValue::Boolean(BooleanRepr {
+                value: repr.value,
+                spelling: Cow::Owned(word.to_string()),
+            }),
+            _ => value.clone(),
+        });
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum NumberWidth {
+    #[default]
+    I32,
+    I64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CommentStyle {
+    DoubleSlash,
+    Hash,
+    DoubleDash,
+    // `/* ... */`, which may span lines. Nesting is not supported: the first
+    // `*/` closes the comment, as in C.
+    Block,
+}
+
+impl CommentStyle {
+    fn introducer(&self) -> &'static [u8] {
+        match self {
+            CommentStyle::DoubleSlash => b"//",
+            CommentStyle::Hash => b"#",
+            CommentStyle::DoubleDash => b"--",
+            CommentStyle::Block => b"/*",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OnUnknown {
+    #[default]
+    Error,
+    Replace,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum IdentClass {
+    #[default]
+    Ascii,
+    // Letters and digits from any script. Columns then count characters
+    // rather than bytes, so `αβ + 1` puts the `+` at column 4.
+    Unicode,
+}
+
+#[derive(Debug, Clone)]
+struct LexerOptions {
+    tab_width: usize,
+    bool_style: BoolStyle,
+    intern_identifiers: bool,
+    emit_eof: bool,
+    max_tokens: Option<usize>,
+    strict_numbers: bool,
+    aliases: HashMap<String, TokenValue>,
+    number_width: NumberWidth,
+    comment_styles: Vec<CommentStyle>,
+    on_unknown: OnUnknown,
+    hex_wraps: bool,
+    radix_prefixes: Vec<(String, u32)>,
+    identifier_chars: IdentClass,
+    case_insensitive: bool,
+    max_number_digits: usize,
+    source_id: u32,
+}
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        LexerOptions {
+            tab_width: 1,
+            bool_style: BoolStyle::default(),
+            intern_identifiers: false,
+            emit_eof: false,
+            max_tokens: None,
+            strict_numbers: false,
+            aliases: HashMap::new(),
+            number_width: NumberWidth::default(),
+            comment_styles: Vec::new(),
+            on_unknown: OnUnknown::default(),
+            hex_wraps: false,
+            radix_prefixes: vec![
+                (String::from("0x"), 16),
+                (String::from("0b"), 2),
+                (String::from("0o"), 8),
+            ],
+            identifier_chars: IdentClass::default(),
+            case_insensitive: false,
+            max_number_digits: 64,
+            source_id: 0,
+        }
+    }
+}
+
+// Hands out ids in order of first appearance, so every occurrence of a
+// name after the first is a hash lookup rather than a fresh `String`.
+#[derive(Debug, Clone, Default)]
+pub struct StringInterner {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl StringInterner {
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        return id;
+    }
+
+    // Panics on an id this interner never handed out.
+    pub fn resolve(&self, id: u32) -> &str {
+        return &self.names[id as usize];
+    }
+}
+
+// Cloning snapshots the cursor so a parser can lex ahead and backtrack.
+// The interner comes along too, but it stays empty unless interning is on.
+#[derive(Clone)]
 pub struct Lexer<'a> {
     cstream: &'a[u8],
     line: usize,
     column: usize,
-    it: usize
+    it: usize,
+    options: LexerOptions,
+    failed: bool,
+    interner: StringInterner,
+    start_line: usize,
+    start_column: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LexerBuilder {
+    options: LexerOptions,
+}
+
+impl LexerBuilder {
+    pub fn new() -> LexerBuilder {
+        return LexerBuilder::default();
+    }
+
+    pub fn tab_width(mut self, tab_width: usize) -> LexerBuilder {
+        self.options.tab_width = tab_width;
+        return self;
+    }
+
+    pub fn bool_style(mut self, bool_style: BoolStyle) -> LexerBuilder {
+        self.options.bool_style = bool_style;
+        return self;
+    }
+
+    pub fn intern_identifiers(mut self, intern_identifiers: bool) -> LexerBuilder {
+        self.options.intern_identifiers = intern_identifiers;
+        return self;
+    }
+
+    pub fn emit_eof(mut self, emit_eof: bool) -> LexerBuilder {
+        self.options.emit_eof = emit_eof;
+        return self;
+    }
+
+    // The `Eof` sentinel from `emit_eof` counts against the limit too.
+    pub fn max_tokens(mut self, max_tokens: usize) -> LexerBuilder {
+        self.options.max_tokens = Some(max_tokens);
+        return self;
+    }
+
+    pub fn strict_numbers(mut self, strict_numbers: bool) -> LexerBuilder {
+        self.options.strict_numbers = strict_numbers;
+        return self;
+    }
+
+    // With `I64`, number literals lex as `Number64` instead of `Number`.
+    pub fn number_width(mut self, number_width: NumberWidth) -> LexerBuilder {
+        self.options.number_width = number_width;
+        return self;
+    }
+
+    // Enables one more comment style, either a line comment or the `Block`
+    // style; call again to allow several.
+    pub fn comment_style(mut self, comment_style: CommentStyle) -> LexerBuilder {
+        if !self.options.comment_styles.contains(&comment_style) {
+            self.options.comment_styles.push(comment_style);
+        }
+        return self;
+    }
+
+    // With `Replace`, each byte nothing else matches becomes an `Unknown`
+    // token and lexing carries on from the next byte.
+    pub fn on_unknown(mut self, on_unknown: OnUnknown) -> LexerBuilder {
+        self.options.on_unknown = on_unknown;
+        return self;
+    }
+
+    // Adds a literal prefix such as `$` for hex, or rebinds an existing one.
+    // `0x`, `0b` and `0o` are registered by default. A prefix may start with
+    // `0` like those, but not with any other digit, which would swallow
+    // ordinary decimal literals. Panics on an empty prefix, a prefix led by
+    // `1`-`9`, or a radix outside 2 to 36.
+    pub fn radix_prefix(mut self, prefix: &str, radix: u32) -> LexerBuilder {
+        assert!((2..=36).contains(&radix), "radix must be between 2 and 36");
+        assert!(!prefix.is_empty(), "radix prefix must not be empty");
+        assert!(!matches!(prefix.as_bytes()[0], b'1'..=b'9'), "radix prefix must not start with a non-zero digit");
+        self.options.radix_prefixes.retain(|(p, _)| p != prefix);
+        self.options.radix_prefixes.push((prefix.to_string(), radix));
+        return self;
+    }
+
+    pub fn clear_radix_prefixes(mut self) -> LexerBuilder {
+        self.options.radix_prefixes.clear();
+        return self;
+    }
+
+    // Lets prefixed literals use the full unsigned range of the number
+    // width, reinterpreted as two's complement: `0xFFFFFFFF` lexes as `-1`.
+    // Decimal literals are always checked against the signed range.
+    pub fn hex_wraps(mut self, hex_wraps: bool) -> LexerBuilder {
+        self.options.hex_wraps = hex_wraps;
+        return self;
+    }
+
+    pub fn identifier_chars(mut self, identifier_chars: IdentClass) -> LexerBuilder {
+        self.options.identifier_chars = identifier_chars;
+        return self;
+    }
+
+    // Accepts keywords and radix prefixes in any case, such as `TRUE` and
+    // `0XAb`. Hex digits are case-insensitive either way.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> LexerBuilder {
+        self.options.case_insensitive = case_insensitive;
+        return self;
+    }
+
+    // Digits after a radix prefix count too; the prefix itself does not.
+    pub fn max_number_digits(mut self, max_number_digits: usize) -> LexerBuilder {
+        self.options.max_number_digits = max_number_digits;
+        return self;
+    }
+
+    // Tags every token with `source_id`, so tokens from several inputs can
+    // be told apart once they are mixed together.
+    pub fn source_id(mut self, source_id: u32) -> LexerBuilder {
+        self.options.source_id = source_id;
+        return self;
+    }
+
+    // Registers a word such as `and` that lexes as `value` wherever it would
+    // otherwise be an identifier.
+    pub fn alias(mut self, word: &str, value: TokenValue) -> LexerBuilder {
+        self.options.aliases.insert(word.to_string(), value);
+        return self;
+    }
+
+    pub fn build(self, cstream: &[u8]) -> Lexer<'_> {
+        let mut lexer = Lexer::from_cstream(cstream);
+        lexer.options = self.options;
+        return lexer;
+    }
+}
+
+pub struct TokenStream(Vec<Token>);
+
+impl TokenStream {
+    pub fn len(&self) -> usize {
+        return self.0.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.0.is_empty();
+    }
+
+    // Skips whitespace, the only trivia the lexer produces today.
+    pub fn significant(&self) -> impl Iterator<Item = &Token> {
+        return self.0.iter().filter(|tok| tok.category() != TokenCategory::Whitespace);
+    }
+
+    // The tokens before the first `Unknown`, which only appears under
+    // `OnUnknown::Replace`. Any other stream is returned whole.
+    pub fn first_error_free(&self) -> &[Token] {
+        let end = self.0.iter()
+            .position(|tok| tok.kind() == TokenKind::Unknown)
+            .unwrap_or(self.0.len());
+        return &self.0[..end];
+    }
+
+    pub fn into_vec(self) -> Vec<Token> {
+        return self.0;
+    }
+}
+
+impl From<Vec<Token>> for TokenStream {
+    fn from(tokens: Vec<Token>) -> Self {
+        TokenStream(tokens)
+    }
+}
+
+impl Deref for TokenStream {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        return &self.0;
+    }
+}
+
+impl IntoIterator for TokenStream {
+    type Item = Token;
+    type IntoIter = std::vec::IntoIter<Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.0.into_iter();
+    }
+}
+
+// Prints like the bare `Vec` it wraps so `debug` output is unchanged.
+impl fmt::Debug for TokenStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return self.0.fmt(f);
+    }
 }
 
-type TokenStream = Vec<Token>;
 type LexerResult<T> = Result<T, LexerError>;
 
+// Common look-alikes for the operators we do understand. An entry matches
+// when the offending byte falls anywhere inside the pattern, so `=<` is
+// found even though the `=` before the `<` lexed fine on its own.
+const SUGGESTIONS: &[(&str, &str)] = &[
+    ("=<", "<="),
+    ("\u{00f7}", "/"),
+    ("\u{00d7}", "*"),
+    ("\u{2212}", "-"),
+    ("\u{2260}", "!="),
+    ("&", "&&"),
+    ("|", "||"),
+];
+
 impl<'a> Lexer<'a> {
     pub fn from_cstream(cstream: &'a[u8]) -> Lexer<'a> {
         Lexer {
@@ -57,26 +839,63 @@ impl<'a> Lexer<'a> {
             it: 0,
             line: 1,
             column: 1,
+            options: LexerOptions::default(),
+            failed: false,
+            interner: StringInterner::default(),
+            start_line: 1,
+            start_column: 1,
         }
     }
 
+    // For a snippet cut out of a larger file: positions are reported as if
+    // the snippet began at `start_line`/`start_col` there. The column offset
+    // only applies to the snippet's first line.
+    pub fn with_line_offset(cstream: &'a[u8], start_line: usize, start_col: usize) -> Lexer<'a> {
+        let mut lexer = Lexer::from_cstream(cstream);
+        lexer.start_line = start_line;
+        lexer.start_column = start_col;
+        lexer.line = start_line;
+        lexer.column = start_col;
+        return lexer;
+    }
+
+    // A lexer over no input at all; `execute` on it yields no tokens.
+    pub fn empty() -> Lexer<'static> {
+        return Lexer::from_cstream(&[]);
+    }
+
+    pub fn lines_with_tokens(cstream: &'a[u8]) -> LinesWithTokens<'a> {
+        return LinesWithTokens {
+            rest: cstream,
+            line: 1,
+            done: false,
+        };
+    }
+
+    pub fn resolve(&self, id: u32) -> &str {
+        return self.interner.resolve(id);
+    }
+
+    pub fn interner(&self) -> &StringInterner {
+        return &self.interner;
+    }
+
     fn get_line(&self) -> usize {
         return self.cstream[0..self.it].iter()
-                                        .map(|b| if *b == b'\n' { 1 as usize } else { 0 as usize })
+                                        .map(|b| if *b == b'\n' { 1 } else { 0 })
                                         .reduce(|a, b| a + b)
-                                        .unwrap_or(0) + 1;
+                                        .unwrap_or(0) + self.start_line;
     }
 
     fn get_column(&self) -> usize {
-        return self.cstream[0..self.it].iter()
-                    .enumerate()
-                    .filter(|n| *(n.1) == b'\n')
-                    .map(|e| e.0).last()
-                    .map_or(self.it + 1, |n| self.it - n);
-    }
-
-    fn get_position(&self) -> (usize, usize) {
-        return (self.get_line(), self.get_column());
+        let (line_start, first_column) = self.cstream[0..self.it].iter()
+                    .rposition(|b| *b == b'\n')
+                    .map_or((0, self.start_column), |n| (n + 1, 1));
+        let unicode = self.options.identifier_chars == IdentClass::Unicode;
+        return self.cstream[line_start..self.it].iter()
+                    .filter(|b| !unicode || **b & 0xC0 != 0x80)
+                    .map(|b| if *b == b'\t' { self.options.tab_width } else { 1 })
+                    .sum::<usize>() + first_column;
     }
 
 
@@ -86,34 +905,187 @@ impl<'a> Lexer<'a> {
         self.line = self.get_line();
     }
 
+    // ASCII digits only, counted the same way as in `check_number_width`.
     fn try_extract_number(&mut self) -> Option<Token> {
-        let regex: Regex = Regex::new(r"^\d+").ok()?;
-        let m = regex.find(&self.cstream[self.it..])?;
-        let val: i32 = m.as_bytes()
-                        .into_iter()
-                        .map(|b| b - b'0')
-                        .map(|b| b as i32)
-                        .reduce(|a, b| a * 10 + b)
-                        .unwrap_or(0);
-        let (line, column) = self.get_position();
-        self.move_curs(m.end());
+        let len = self.cstream[self.it..].iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if len == 0 {
+            return None;
+        }
+        let val = self.cstream[self.it..self.it + len].iter()
+            .try_fold(0i64, |a, b| a.checked_mul(10)?.checked_add((b - b'0') as i64))?;
+        let value = match self.options.number_width {
+            NumberWidth::I32 => TokenValue::Number(i32::try_from(val).ok()?),
+            NumberWidth::I64 => TokenValue::Number64(val),
+        };
+        let (line, column) = (self.line, self.column);
+        let start = self.it;
+        self.move_curs(len);
         return Some(Token {
-            value: TokenValue::Number(val),
+            value,
+            span: start..self.it,
+            source_id: self.options.source_id,
+            end_line: self.line,
+            end_column: self.column,
             line,
             column
         });
 
     }
 
+    // A lone `0` is fine; `007` is not. `0xFF` only has the one leading
+    // digit, so prefixed literals never trip this.
+    fn check_leading_zero(&self) -> LexerResult<()> {
+        let digits = self.cstream[self.it..].iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits > 1 && self.cstream[self.it] == b'0' {
+            return Err(LexerError {
+                value: LexerErrorValue::LeadingZero,
+                line: self.line,
+                column: self.column,
+                suggestion: None,
+            });
+        }
+        return Ok(());
+    }
+
+    // The longest registered prefix followed by at least one digit wins. A
+    // bare `0x` with no digits after it is left to lex as `0` followed by
+    // an identifier, as it did before prefixes existed.
+    fn try_extract_radix_number(&mut self) -> LexerResult<Option<Token>> {
+        let rest = &self.cstream[self.it..];
+        let found = self.options.radix_prefixes.iter()
+            .filter(|(prefix, _)| match rest.get(..prefix.len()) {
+                Some(head) if self.options.case_insensitive => head.eq_ignore_ascii_case(prefix.as_bytes()),
+                Some(head) => head == prefix.as_bytes(),
+                None => false,
+            })
+            .map(|(prefix, radix)| {
+                let len = rest[prefix.len()..].iter()
+                    .take_while(|b| (**b as char).is_digit(*radix))
+                    .count();
+                (prefix.len(), *radix, len)
+            })
+            .filter(|(_, _, len)| *len > 0)
+            .max_by_key(|(prefix_len, _, _)| *prefix_len);
+        let (prefix_len, radix, len) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let digits = &rest[prefix_len..];
+        self.check_number_length(len)?;
+        let text = String::from_utf8_lossy(&digits[..len]);
+        let val = u64::from_str_radix(&text, radix).ok();
+        let value = match (val, self.options.number_width, self.options.hex_wraps) {
+            (Some(val), NumberWidth::I32, false) => i32::try_from(val).ok().map(TokenValue::Number),
+            (Some(val), NumberWidth::I32, true) => u32::try_from(val).ok().map(|v| TokenValue::Number(v as i32)),
+            (Some(val), NumberWidth::I64, false) => i64::try_from(val).ok().map(TokenValue::Number64),
+            (Some(val), NumberWidth::I64, true) => Some(TokenValue::Number64(val as i64)),
+            (None, _, _) => None,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Err(LexerError {
+                value: LexerErrorValue::NumberOverflow,
+                line: self.line,
+                column: self.column,
+                suggestion: None,
+            }),
+        };
+        let (line, column) = (self.line, self.column);
+        let start = self.it;
+        self.move_curs(prefix_len + len);
+        return Ok(Some(Token {
+            value,
+            span: start..self.it,
+            source_id: self.options.source_id,
+            end_line: self.line,
+            end_column: self.column,
+            line, column
+        }));
+    }
+
+    // Rejects absurdly long literals before any digit is accumulated.
+    fn check_number_length(&self, digits: usize) -> LexerResult<()> {
+        if digits > self.options.max_number_digits {
+            return Err(LexerError {
+                value: LexerErrorValue::NumberTooLong,
+                line: self.line,
+                column: self.column,
+                suggestion: None,
+            });
+        }
+        return Ok(());
+    }
+
+    // Run before `try_extract_number` so a literal that does not fit is
+    // reported here instead of being skipped by the extractor.
+    fn check_number_width(&self) -> LexerResult<()> {
+        let digits = self.cstream[self.it..].iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        self.check_number_length(digits)?;
+        let val = self.cstream[self.it..self.it + digits].iter()
+            .try_fold(0i64, |a, b| a.checked_mul(10)?.checked_add((b - b'0') as i64));
+        let fits = match (val, self.options.number_width) {
+            (Some(val), NumberWidth::I32) => i32::try_from(val).is_ok(),
+            (Some(_), NumberWidth::I64) => true,
+            (None, _) => false,
+        };
+        if !fits {
+            return Err(LexerError {
+                value: LexerErrorValue::NumberOverflow,
+                line: self.line,
+                column: self.column,
+                suggestion: None,
+            });
+        }
+        return Ok(());
+    }
+
+    // Comments produce no token. A line comment stops on its newline, which
+    // is left to lex as ordinary whitespace. Loops so back-to-back comments
+    // such as `/* a *//* b */` are all skipped before the next token.
+    fn skip_comments(&mut self) -> LexerResult<()> {
+        loop {
+            let rest = &self.cstream[self.it..];
+            let style = self.options.comment_styles.iter()
+                .find(|style| rest.starts_with(style.introducer()));
+            let len = match style {
+                Some(CommentStyle::Block) => {
+                    match rest.windows(2).skip(2).position(|w| w == b"*/") {
+                        Some(n) => n + 4,
+                        None => return Err(LexerError {
+                            value: LexerErrorValue::UnterminatedComment,
+                            line: self.line,
+                            column: self.column,
+                            suggestion: None,
+                        }),
+                    }
+                },
+                Some(_) => rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len()),
+                None => return Ok(()),
+            };
+            self.move_curs(len);
+        }
+    }
+
     fn try_extract_whitespace(&mut self) -> Option<Token> {
         let regex = Regex::new(r"^\s+").unwrap();
         match regex.find(&self.cstream[self.it..]) {
             Some(m) => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(m.end());
                 Some(Token {
                     value: TokenValue::Whitespace,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -128,9 +1100,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'=' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::Equal,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -139,9 +1116,14 @@ impl<'a> Lexer<'a> {
 
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::Cross,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -149,9 +1131,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'-' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::Dash,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -159,9 +1146,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'*' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::Star,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -169,9 +1161,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'/' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::Slash,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -179,9 +1176,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'(' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::OpenRoundBracket,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -189,9 +1191,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b')' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::CloseRoundBracket,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -199,9 +1206,14 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'{' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::OpenCurlyBracket,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
@@ -209,58 +1221,162 @@ impl<'a> Lexer<'a> {
             Some(b) if *b == b'}' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
                     value: TokenValue::CloseCurlyBracket,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
             },
-            Some(b) if *b == b'!' => {
+            Some(b) if *b == b'@' => {
                 let line = self.line;
                 let column = self.column;
+                let start = self.it;
                 self.move_curs(1);
                 Some(Token{
-                    value: TokenValue::CloseCurlyBracket,
+                    value: TokenValue::At,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line,
                     column
                 })
             },
-            _ => None
-        }
-    }
-
-    fn try_extract_doubles(&mut self) -> Option<Token> {
-        match self.cstream.get(self.it..self.it+2) {
-            Some(val) if *val == b"=="[..] => {
-                let (line, column) = (self.line, self.column);
-                self.move_curs(2);
-                return Some(Token{
-                    value: TokenValue::DoubleEqual,
-                    line, column
-                });
+            // Only reached with `#` comments off, as `skip_comments` runs first.
+            Some(b) if *b == b'#' => {
+                let line = self.line;
+                let column = self.column;
+                let start = self.it;
+                self.move_curs(1);
+                Some(Token{
+                    value: TokenValue::Hash,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line,
+                    column
+                })
+            },
+            Some(b) if *b == b';' => {
+                let line = self.line;
+                let column = self.column;
+                let start = self.it;
+                self.move_curs(1);
+                Some(Token{
+                    value: TokenValue::Semicolon,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line,
+                    column
+                })
+            },
+            Some(b) if *b == b'~' => {
+                let line = self.line;
+                let column = self.column;
+                let start = self.it;
+                self.move_curs(1);
+                Some(Token{
+                    value: TokenValue::Tilde,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line,
+                    column
+                })
+            },
+            Some(b) if *b == b'!' => {
+                let line = self.line;
+                let column = self.column;
+                let start = self.it;
+                self.move_curs(1);
+                Some(Token{
+                    value: TokenValue::Excl,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line,
+                    column
+                })
+            },
+            _ => None
+        }
+    }
+
+    fn try_extract_doubles(&mut self) -> Option<Token> {
+        match self.cstream.get(self.it..self.it+2) {
+            Some(val) if *val == b"=="[..] => {
+                let (line, column) = (self.line, self.column);
+                let start = self.it;
+                self.move_curs(2);
+                return Some(Token{
+                    value: TokenValue::DoubleEqual,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line, column
+                });
             }
             Some(val) if *val == b"!="[..] => {
                 let (line, column) = (self.line, self.column);
+                let start = self.it;
                 self.move_curs(2);
                 return Some(Token{
                     value: TokenValue::ExclEqual,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line, column
                 });
             }
             Some(val) if *val == b"&&"[..] => {
                 let (line, column) = (self.line, self.column);
+                let start = self.it;
                 self.move_curs(2);
                 return Some(Token{
                     value: TokenValue::DoubleAnd,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line, column
                 });
             }
             Some(val) if *val == b"||"[..] => {
                 let (line, column) = (self.line, self.column);
+                let start = self.it;
                 self.move_curs(2);
                 return Some(Token{
                     value: TokenValue::DoublePipe,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
+                    line, column
+                });
+            }
+            Some(val) if *val == b"|>"[..] => {
+                let (line, column) = (self.line, self.column);
+                let start = self.it;
+                self.move_curs(2);
+                return Some(Token{
+                    value: TokenValue::PipeForward,
+                    span: start..self.it,
+                    source_id: self.options.source_id,
+                    end_line: self.line,
+                    end_column: self.column,
                     line, column
                 });
             }
@@ -269,106 +1385,1819 @@ impl<'a> Lexer<'a> {
         return None;
     }
 
-    fn try_extract_boolean(&mut self) -> Option<Token> {
-        let bool_true = b"True";
-        let bool_false = b"False";
-        match self.cstream.get(self.it..self.it+bool_true.len()) {
-            Some(val) if *val == bool_true[..] => {
-                let line = self.line;
-                let column = self.column;
-                self.move_curs(bool_true.len());
-                return Some(Token{
-                    value: TokenValue::Boolean(true),
-                    line, column
-                });
-            },
-            _ => ()
+    // Only the valid UTF-8 prefix of the rest of the stream is decoded, so a
+    // stray invalid byte further on does not hide an identifier before it.
+    fn unicode_identifier_len(rest: &[u8]) -> usize {
+        let text = match std::str::from_utf8(rest) {
+            Ok(text) => text,
+            Err(e) => std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap(),
+        };
+        let mut chars = text.char_indices();
+        match chars.next() {
+            Some((_, c)) if c.is_alphabetic() || c == '_' => (),
+            _ => return 0,
         };
+        return chars.find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(text.len(), |(i, _)| i);
+    }
 
-        match self.cstream.get(self.it..self.it+bool_false.len()) {
-            Some(val) if *val == bool_false[..] => {
-                let (line, column) = (self.get_line(), self.get_column());
-                self.move_curs(bool_false.len());
-                return Some(Token {
-                    value: TokenValue::Boolean(false),
-                    line, column
-                });
+    fn try_extract_identifier(&mut self) -> Option<Token> {
+        let rest = &self.cstream[self.it..];
+        let len = match self.options.identifier_chars {
+            IdentClass::Ascii => match rest.first() {
+                Some(b) if b.is_ascii_alphabetic() || *b == b'_' => rest.iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+                    .count(),
+                _ => 0,
             },
-            _ => ()
+            IdentClass::Unicode => Lexer::unicode_identifier_len(rest),
+        };
+        if len == 0 {
+            return None;
+        }
+        let word = String::from_utf8_lossy(&rest[..len]);
+        // Aliases are the user's own words, so they win over keywords.
+        let reserved = self.options.aliases.get(word.as_ref()).cloned()
+            .or_else(|| lookup_keyword(&word, self.options.bool_style, self.options.case_insensitive));
+        let value = match (reserved, word.as_ref()) {
+            (Some(value), _) => value,
+            (None, "_") => TokenValue::Underscore,
+            (None, name) if self.options.intern_identifiers => {
+                TokenValue::Symbol(self.interner.intern(name))
+            },
+            (None, name) => TokenValue::Identifier(name.to_string()),
+        };
+        let (line, column) = (self.line, self.column);
+        let start = self.it;
+        self.move_curs(len);
+        return Some(Token {
+            value,
+            span: start..self.it,
+            source_id: self.options.source_id,
+            end_line: self.line,
+            end_column: self.column,
+            line, column
+        });
+    }
+
+    // `Ok(None)` means end of input. Empty input, or input that is only
+    // comments, reaches it on the first call, and every later call returns
+    // it again.
+    pub fn lex_one(&mut self) -> LexerResult<Option<Token>> {
+        self.skip_comments()?;
+        if self.it >= self.cstream.len() {
+            return Ok(None);
+        }
+
+        match self.try_extract_radix_number()? {
+            Some(tok) => return Ok(Some(tok)),
+            None => (),
+        };
+
+        if self.options.strict_numbers {
+            self.check_leading_zero()?;
+        }
+        self.check_number_width()?;
+
+        match self.try_extract_number() {
+            Some(tok) => return Ok(Some(tok)),
+            None => (),
+        };
+
+        match self.try_extract_whitespace() {
+            Some(tok) => return Ok(Some(tok)),
+            None => (),
+        };
+
+        match self.try_extract_doubles() {
+            Some(tok) => return Ok(Some(tok)),
+            None => ()
+        };
+
+        match self.try_extract_singles() {
+            Some(tok) => return Ok(Some(tok)),
+            None => ()
+        };
+
+        match self.try_extract_identifier() {
+            Some(tok) => return Ok(Some(tok)),
+            None => ()
+        };
+
+        if self.options.on_unknown == OnUnknown::Replace {
+            let (line, column) = (self.line, self.column);
+            let byte = self.cstream[self.it];
+            let start = self.it;
+            self.move_curs(1);
+            return Ok(Some(Token {
+                value: TokenValue::Unknown(byte),
+                span: start..self.it,
+                source_id: self.options.source_id,
+                end_line: self.line,
+                end_column: self.column,
+                line, column
+            }));
+        }
+
+        // Backslashes are reserved for escapes inside future string and
+        // character literals, so a bare one gets its own error.
+        let value = match self.cstream[self.it] {
+            b'\\' => LexerErrorValue::StrayBackslash,
+            _ => LexerErrorValue::UnrecognizedToken,
         };
+        return Err(LexerError {
+            value,
+            line: self.line,
+            column: self.column,
+            suggestion: self.suggest(),
+        });
+    }
 
+    fn suggest(&self) -> Option<&'static str> {
+        for (pattern, suggestion) in SUGGESTIONS {
+            let pattern = pattern.as_bytes();
+            for back in 0..pattern.len().min(self.it + 1) {
+                if self.cstream[self.it - back..].starts_with(pattern) {
+                    return Some(suggestion);
+                }
+            }
+        }
         return None;
     }
 
+    // Empty input is not an error: it gives `Ok` with no tokens, or just the
+    // `Eof` sentinel when `emit_eof` is on.
     pub fn execute(&mut self) -> LexerResult<TokenStream> {
         let mut tok_stream: Vec<Token> = Vec::new();
+        self.execute_into(&mut tok_stream)?;
+        return Ok(TokenStream(tok_stream));
+    }
 
-        while self.it < self.cstream.len() {
+    // Clears `tok_stream` first but keeps its capacity, so one buffer can be
+    // reused across many small inputs. On error it holds the tokens lexed
+    // before the failure.
+    pub fn execute_into(&mut self, tok_stream: &mut Vec<Token>) -> LexerResult<()> {
+        tok_stream.clear();
 
-            match self.try_extract_number() {
-                Some(tok) => { 
-                    tok_stream.push(tok);
-                    continue;
+        while let Some(tok) = self.lex_one()? {
+            // The error points at the first token past the limit.
+            match self.options.max_tokens {
+                Some(max) if tok_stream.len() >= max => {
+                    return Err(LexerError {
+                        value: LexerErrorValue::TooManyTokens,
+                        line: tok.line,
+                        column: tok.column,
+                        suggestion: None,
+                    });
                 },
-                None => (),
+                _ => (),
             };
+            tok_stream.push(tok);
+        }
 
-            match self.try_extract_whitespace() {
-                Some(tok) => {
-                    tok_stream.push(tok);
-                    continue;
+        if self.options.emit_eof {
+            match self.options.max_tokens {
+                Some(max) if tok_stream.len() >= max => {
+                    return Err(LexerError {
+                        value: LexerErrorValue::TooManyTokens,
+                        line: self.line,
+                        column: self.column,
+                        suggestion: None,
+                    });
                 },
-                None => (),
+                _ => (),
             };
+            tok_stream.push(Token {
+                value: TokenValue::Eof,
+                span: self.it..self.it,
+                source_id: self.options.source_id,
+                end_line: self.line,
+                end_column: self.column,
+                line: self.line,
+                column: self.column,
+            });
+        }
 
-            match self.try_extract_doubles() {
-                Some(tok) => {
-                    tok_stream.push(tok);
-                    continue;
-                }
-                None => ()
-            };
+        return Ok(());
+    }
 
-            match self.try_extract_singles() {
-                Some(tok) => {
-                    tok_stream.push(tok);
-                    continue;
-                },
-                None => ()
+    // Unlike the `max_tokens` limit this is not an error: lexing simply
+    // stops after `n` tokens and the cursor is left where it stopped.
+    pub fn execute_prefix(&mut self, n: usize) -> LexerResult<TokenStream> {
+        let mut tok_stream: Vec<Token> = Vec::new();
+
+        while tok_stream.len() < n {
+            match self.lex_one()? {
+                Some(tok) => tok_stream.push(tok),
+                None => break,
             };
+        }
+
+        return Ok(TokenStream(tok_stream));
+    }
 
-            match self.try_extract_boolean() {
-                Some(tok) => {
-                    tok_stream.push(tok);
-                    continue;
+    // Returns `(category, start, end)` byte ranges covering the rest of the
+    // input. Comments are the only gaps between token spans, so each gap is
+    // reported as `Comment`; after a lexer error the remainder is `Error`.
+    pub fn highlight(&mut self) -> Vec<(HighlightCategory, usize, usize)> {
+        let mut ranges: Vec<(HighlightCategory, usize, usize)> = Vec::new();
+        let mut end = self.it;
+        loop {
+            let next = self.lex_one();
+            let start = match &next {
+                Ok(Some(tok)) => tok.span.start,
+                _ => self.it,
+            };
+            if start > end {
+                ranges.push((HighlightCategory::Comment, end, start));
+            }
+            match next {
+                Ok(Some(tok)) => {
+                    ranges.push((highlight_category(&tok.value), tok.span.start, tok.span.end));
+                    end = tok.span.end;
+                },
+                Ok(None) => break,
+                Err(_) => {
+                    ranges.push((HighlightCategory::Error, self.it, self.cstream.len()));
+                    break;
                 },
-                None => ()
             };
-
-            return Err(LexerError {
-                value: LexerErrorValue::UnrecognizedToken,
-                line: self.line,
-                column: self.column
-            });
         }
+        return ranges;
+    }
 
-        return Ok(tok_stream);
+    // Pushes each token to `f` instead of collecting them. Returning
+    // `ControlFlow::Break` stops lexing with the cursor just past that token.
+    pub fn lex_events<F: FnMut(&Token) -> ControlFlow<()>>(&mut self, mut f: F) -> LexerResult<()> {
+        while let Some(tok) = self.lex_one()? {
+            if f(&tok).is_break() {
+                break;
+            }
+        }
+        return Ok(());
+    }
 
+    // Lexes on a clone, so `self` is left exactly where it was.
+    pub fn peek_n(&self, n: usize) -> LexerResult<Vec<Token>> {
+        return self.clone().execute_prefix(n).map(TokenStream::into_vec);
     }
 
     pub fn debug(&mut self) {
         let res: LexerResult<TokenStream> = self.execute();
-        match res {
+        match &res {
             Ok(tokens) => {
                 println!("{:#?}", tokens);
             },
+            Err(_) => ()
+        }
+        println!("{}", summarize(&res));
+    }
+}
+
+impl<'a> Default for Lexer<'a> {
+    fn default() -> Self {
+        Lexer::from_cstream(&[])
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = LexerResult<Token>;
+
+    // The cursor does not move past an unrecognized token, so the iterator
+    // stops after yielding the first error instead of repeating it forever.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.lex_one() {
+            Ok(tok) => tok.map(Ok),
             Err(e) => {
-                println!("{:?}", e);
+                self.failed = true;
+                Some(Err(e))
             }
         }
     }
 }
 
+// Cheaper to call than `execute` when only the verdict matters, since the
+// tokens are dropped as soon as they are produced.
+pub fn is_valid(input: &[u8]) -> bool {
+    return Lexer::from_cstream(input).all(|res| res.is_ok());
+}
+
+// The offset is where the failing token would have started, which is
+// `input.len()` when the whole input lexes.
+pub fn longest_valid_prefix(input: &[u8]) -> (TokenStream, usize) {
+    let mut lexer = Lexer::from_cstream(input);
+    let mut tok_stream: Vec<Token> = Vec::new();
+    loop {
+        let start = lexer.it;
+        match lexer.lex_one() {
+            Ok(Some(tok)) => tok_stream.push(tok),
+            Ok(None) => return (TokenStream(tok_stream), input.len()),
+            Err(_) => return (TokenStream(tok_stream), start),
+        };
+    }
+}
+
+// Each line gets its own lexer, so positions restart at line 1 and an
+// unrecognized token only spoils the line it appears on.
+pub fn tokenize_lines(input: &str) -> Vec<LexerResult<TokenStream>> {
+    return input.split('\n')
+        .map(|line| Lexer::from_cstream(line.as_bytes()).execute())
+        .collect();
+}
+
+// Everything between the runs is skipped a byte at a time without being
+// lexed, so input the lexer would reject still gets a full map.
+pub fn whitespace_map(input: &[u8]) -> Vec<(usize, usize, usize)> {
+    let mut lexer = Lexer::from_cstream(input);
+    let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+    while lexer.it < input.len() {
+        let start = lexer.it;
+        match lexer.try_extract_whitespace() {
+            Some(tok) => runs.push((tok.line, tok.column, lexer.it - start)),
+            None => lexer.move_curs(1),
+        }
+    }
+    return runs;
+}
+
+// Positions are dropped so two streams can be compared on shape alone.
+pub fn values(tokens: &[Token]) -> Vec<TokenValue> {
+    return tokens.iter().map(|tok| tok.value.clone()).collect();
+}
+
+const OPERATOR_BYTES: &[u8] = b"+-*/=!&|<>%^~";
+
+// A cluster is a maximal run of operator bytes; it is fine when it lexes to
+// exactly one token, so `==` passes while `=+` and `!!` are reported.
+pub fn validate_operators(input: &[u8]) -> Vec<(usize, usize, Vec<u8>)> {
+    let mut lexer = Lexer::from_cstream(input);
+    let mut invalid: Vec<(usize, usize, Vec<u8>)> = Vec::new();
+    while lexer.it < input.len() {
+        let len = input[lexer.it..].iter()
+            .take_while(|b| OPERATOR_BYTES.contains(b))
+            .count();
+        if len == 0 {
+            lexer.move_curs(1);
+            continue;
+        }
+        let cluster = &input[lexer.it..lexer.it + len];
+        match Lexer::from_cstream(cluster).execute() {
+            Ok(tokens) if tokens.len() == 1 => (),
+            _ => invalid.push((lexer.line, lexer.column, cluster.to_vec())),
+        };
+        lexer.move_curs(len);
+    }
+    return invalid;
+}
+
+// Entries are `(byte_start, token_index)` in source order, so the token
+// covering a cursor offset is found with a binary search on `byte_start`.
+pub fn with_index(input: &[u8]) -> LexerResult<(TokenStream, Vec<(usize, usize)>)> {
+    let mut lexer = Lexer::from_cstream(input);
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut index: Vec<(usize, usize)> = Vec::new();
+    loop {
+        lexer.skip_comments()?;
+        let start = lexer.it;
+        match lexer.lex_one()? {
+            Some(tok) => {
+                index.push((start, tokens.len()));
+                tokens.push(tok);
+            },
+            None => break,
+        };
+    }
+    return Ok((TokenStream(tokens), index));
+}
+
+#[derive(Debug)]
+pub struct BracketError {
+    value: BracketErrorValue,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BracketErrorValue {
+    Unclosed,
+    UnexpectedClose,
+    Mismatched,
+}
+
+impl BracketError {
+    pub fn value(&self) -> BracketErrorValue {
+        return self.value;
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+}
+
+// Unclosed brackets are reported at the innermost one left open; the other
+// errors are reported at the offending close bracket.
+pub fn check_brackets(tokens: &[Token]) -> Result<(), BracketError> {
+    let mut open: Vec<&Token> = Vec::new();
+    for tok in tokens {
+        let expected = match tok.value {
+            TokenValue::OpenRoundBracket | TokenValue::OpenCurlyBracket => {
+                open.push(tok);
+                continue;
+            },
+            TokenValue::CloseRoundBracket => TokenValue::OpenRoundBracket,
+            TokenValue::CloseCurlyBracket => TokenValue::OpenCurlyBracket,
+            _ => continue,
+        };
+        let value = match open.pop() {
+            Some(opener) if opener.value == expected => continue,
+            Some(_) => BracketErrorValue::Mismatched,
+            None => BracketErrorValue::UnexpectedClose,
+        };
+        return Err(BracketError { value, line: tok.line, column: tok.column });
+    }
+    match open.pop() {
+        Some(opener) => Err(BracketError {
+            value: BracketErrorValue::Unclosed,
+            line: opener.line,
+            column: opener.column,
+        }),
+        None => Ok(()),
+    }
+}
+
+// `tokens` must come from lexing `input` with no comment styles enabled,
+// since skipped comments leave gaps that trip the debug check.
+pub fn reconstruct(input: &[u8], tokens: &[Token]) -> Vec<u8> {
+    let source: Vec<u8> = tokens.iter()
+        .flat_map(|tok| input[tok.span.clone()].iter().copied())
+        .collect();
+    debug_assert_eq!(source, input, "token spans do not cover the input");
+    return source;
+}
+
+// Tokens that only partly overlap `range` are included. The `Eof` sentinel
+// has an empty span and so never overlaps anything.
+pub fn tokens_in_range(tokens: &[Token], range: Range<usize>) -> Vec<&Token> {
+    return tokens.iter()
+        .filter(|tok| tok.span.start < range.end && range.start < tok.span.end)
+        .collect();
+}
+
+pub fn annotate(input: &str) -> LexerResult<Vec<(TokenValue, String)>> {
+    let tokens = Lexer::from_cstream(input.as_bytes()).execute()?;
+    return Ok(tokens.iter()
+        .map(|tok| (tok.value.clone(), input[tok.span.clone()].to_string()))
+        .collect());
+}
+
+// `Number64` payloads are skipped, since they may not fit an `i32`;
+// `sum_numbers` counts them.
+pub fn numbers(tokens: &[Token]) -> Vec<i32> {
+    return tokens.iter()
+        .filter_map(|tok| match tok.value {
+            TokenValue::Number(n) => Some(n),
+            _ => None,
+        })
+        .collect();
+}
+
+// Widened to `i128`, which no stream of `i64` payloads can overflow.
+pub fn sum_numbers(tokens: &[Token]) -> i128 {
+    return tokens.iter()
+        .filter_map(|tok| match tok.value {
+            TokenValue::Number(n) => Some(n as i128),
+            TokenValue::Number64(n) => Some(n as i128),
+            _ => None,
+        })
+        .sum();
+}
+
+// One row per token under a `token | line | column | start | end` header,
+// each column padded to its widest cell.
+pub fn debug_positions<W: Write>(tokens: &[Token], w: &mut W) -> io::Result<()> {
+    let mut rows: Vec<[String; 5]> = vec![[
+        String::from("token"),
+        String::from("line"),
+        String::from("column"),
+        String::from("start"),
+        String::from("end"),
+    ]];
+    for tok in tokens {
+        rows.push([
+            format!("{:?}", tok.value),
+            tok.line.to_string(),
+            tok.column.to_string(),
+            tok.span.start.to_string(),
+            tok.span.end.to_string(),
+        ]);
+    }
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in &rows {
+        let cells: Vec<String> = row.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(w, "{}", cells.join(" | ").trim_end())?;
+    }
+    return Ok(());
+}
+
+// Lexes one source line per `next`, so a huge paste is never held as a
+// single token stream. Token positions keep their real line numbers.
+pub struct LinesWithTokens<'a> {
+    rest: &'a [u8],
+    line: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for LinesWithTokens<'a> {
+    type Item = (usize, LexerResult<Vec<Token>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let source = match self.rest.iter().position(|b| *b == b'\n') {
+            Some(n) => {
+                let source = &self.rest[..n];
+                self.rest = &self.rest[n + 1..];
+                source
+            },
+            None => {
+                self.done = true;
+                self.rest
+            },
+        };
+        let line = self.line;
+        self.line += 1;
+        let tokens = Lexer::with_line_offset(source, line, 1).execute()
+            .map(TokenStream::into_vec);
+        return Some((line, tokens));
+    }
+}
+
+// An operator is binary when the last non-whitespace token before it ends
+// an operand, which leaves a unary minus such as `-1` or `(-1)` alone.
+pub fn check_operator_spacing(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut flagged: Vec<Range<usize>> = Vec::new();
+    for (i, tok) in tokens.iter().enumerate() {
+        if tok.category() != TokenCategory::Operator || tok.is_any(&[TokenKind::Excl, TokenKind::Tilde]) {
+            continue;
+        }
+        let after_operand = tokens[..i].iter()
+            .rfind(|prev| prev.value != TokenValue::Whitespace)
+            .is_some_and(|prev| match prev.value {
+                TokenValue::CloseRoundBracket | TokenValue::CloseCurlyBracket => true,
+                _ => matches!(prev.category(),
+                    TokenCategory::Literal | TokenCategory::Keyword | TokenCategory::Identifier),
+            });
+        if !after_operand {
+            continue;
+        }
+        let spaced_before = tokens[i - 1].value == TokenValue::Whitespace;
+        let spaced_after = tokens.get(i + 1)
+            .is_some_and(|next| next.value == TokenValue::Whitespace);
+        if !spaced_before || !spaced_after {
+            flagged.push(tok.span.clone());
+        }
+    }
+    return flagged;
+}
+
+// A space is kept between two tokens only when gluing them together would
+// lex differently, as with `1 2` or `= =`.
+pub fn minify(input: &str) -> LexerResult<String> {
+    let tokens = Lexer::from_cstream(input.as_bytes()).execute()?;
+    let mut out = String::new();
+    let mut prev: Option<&TokenValue> = None;
+    for tok in tokens.significant() {
+        let text = tok.value.to_string();
+        if let Some(prev) = prev {
+            let glued = format!("{}{}", prev, text);
+            let relexed = Lexer::from_cstream(glued.as_bytes()).execute()
+                .map(|glued| values(&glued));
+            match relexed {
+                Ok(relexed) if relexed == [prev.clone(), tok.value.clone()] => (),
+                _ => out.push(' '),
+            };
+        }
+        out.push_str(&text);
+        prev = Some(&tok.value);
+    }
+    return Ok(out);
+}
+
+// Groups exclude the semicolons themselves. A trailing semicolon does not
+// start an empty final group, and empty input gives no groups at all. Any
+// other empty statement keeps its empty group, so `1;;2` gives three groups
+// and `;` alone gives one.
+pub fn split_at_semicolons(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut groups: Vec<&[Token]> = tokens.split(|tok| tok.value == TokenValue::Semicolon).collect();
+    if groups.last().is_some_and(|group| group.is_empty()) {
+        groups.pop();
+    }
+    return groups;
+}
+
+pub fn operators_used(tokens: &[Token]) -> BTreeSet<TokenKind> {
+    return tokens.iter()
+        .filter(|tok| tok.category() == TokenCategory::Operator)
+        .map(|tok| tok.kind())
+        .collect();
+}
+
+// The outer result is the read, the inner one the lex, so callers can
+// tell a missing file from a malformed one.
+pub fn tokenize_reader<R: Read>(r: &mut R) -> io::Result<LexerResult<TokenStream>> {
+    let mut buf: Vec<u8> = Vec::new();
+    r.read_to_end(&mut buf)?;
+    return Ok(Lexer::from_cstream(&buf).execute());
+}
+
+// The answer is the last token starting at or before the position, as long
+// as the position is before that token's end. Ends are counted the way the
+// lexer counts columns, so a tab covers all of its `tab_width` columns.
+pub fn token_at(tokens: &[Token], line: usize, column: usize) -> Option<&Token> {
+    let idx = tokens.partition_point(|tok| (tok.line, tok.column) <= (line, column));
+    let tok = tokens.get(idx.checked_sub(1)?)?;
+    let covers = (line, column) < (tok.end_line, tok.end_column);
+    return if covers { Some(tok) } else { None };
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Completeness {
+    Complete,
+    Incomplete,
+    Invalid,
+}
+
+// Meant for deciding whether a REPL should keep reading. Input is
+// `Incomplete` while it is empty, leaves a bracket open, or ends on an
+// operator. It is `Invalid` when more input cannot fix it: it fails to lex,
+// closes a bracket it never opened, or uses a token that lexes but has no
+// place in an expression yet (`@`, `#`, `;` and `|>`).
+pub fn is_complete_expression(input: &str) -> Completeness {
+    let tokens = match Lexer::from_cstream(input.as_bytes()).execute() {
+        Ok(tokens) => tokens,
+        Err(_) => return Completeness::Invalid,
+    };
+    let reserved = [TokenKind::At, TokenKind::Hash, TokenKind::Semicolon, TokenKind::PipeForward];
+    if tokens.iter().any(|tok| tok.is_any(&reserved)) {
+        return Completeness::Invalid;
+    }
+    match check_brackets(&tokens) {
+        Ok(()) => (),
+        Err(e) if e.value == BracketErrorValue::Unclosed => return Completeness::Incomplete,
+        Err(_) => return Completeness::Invalid,
+    };
+    match tokens.significant().last() {
+        Some(tok) if tok.category() == TokenCategory::Operator => Completeness::Incomplete,
+        Some(_) => Completeness::Complete,
+        None => Completeness::Incomplete,
+    }
+}
+
+pub fn group_by_line(tokens: &[Token]) -> Vec<(usize, Vec<&Token>)> {
+    let mut groups: Vec<(usize, Vec<&Token>)> = Vec::new();
+    for tok in tokens {
+        match groups.last_mut() {
+            Some((line, group)) if *line == tok.line => group.push(tok),
+            _ => groups.push((tok.line, vec![tok])),
+        }
+    }
+    return groups;
+}
+
+impl TokenValue {
+    fn json_kind(&self) -> &'static str {
+        match self {
+            TokenValue::Number(_) | TokenValue::Number64(_) => "number",
+            TokenValue::Boolean(_) => "boolean",
+            TokenValue::Character(_) => "character",
+            TokenValue::Cross => "cross",
+            TokenValue::Dash => "dash",
+            TokenValue::Star => "star",
+            TokenValue::Slash => "slash",
+            TokenValue::Whitespace => "whitespace",
+            TokenValue::OpenRoundBracket => "open_round_bracket",
+            TokenValue::CloseRoundBracket => "close_round_bracket",
+            TokenValue::OpenCurlyBracket => "open_curly_bracket",
+            TokenValue::CloseCurlyBracket => "close_curly_bracket",
+            TokenValue::Equal => "equal",
+            TokenValue::ExclEqual => "excl_equal",
+            TokenValue::DoubleEqual => "double_equal",
+            TokenValue::DoubleAnd => "double_and",
+            TokenValue::DoublePipe => "double_pipe",
+            TokenValue::PipeForward => "pipe_forward",
+            TokenValue::Tilde => "tilde",
+            TokenValue::Excl => "excl",
+            TokenValue::Identifier(_) => "identifier",
+            TokenValue::Symbol(_) => "symbol",
+            TokenValue::Underscore => "underscore",
+            TokenValue::Eof => "eof",
+            TokenValue::Unknown(_) => "unknown",
+            TokenValue::At => "at",
+            TokenValue::Hash => "hash",
+            TokenValue::Semicolon => "semicolon",
+        }
+    }
+
+    fn json_value(&self) -> String {
+        match self {
+            TokenValue::Number(n) => n.to_string(),
+            TokenValue::Number64(n) => n.to_string(),
+            TokenValue::Boolean(repr) => repr.value.to_string(),
+            TokenValue::Character(c) => c.to_string(),
+            TokenValue::Identifier(name) => format!("\"{}\"", name),
+            TokenValue::Symbol(id) => id.to_string(),
+            TokenValue::Unknown(b) => b.to_string(),
+            _ => String::from("null"),
+        }
+    }
+}
+
+impl LexerErrorValue {
+    pub fn message(&self) -> &'static str {
+        match self {
+            LexerErrorValue::UnrecognizedToken => "unrecognized token",
+            LexerErrorValue::TooManyTokens => "too many tokens",
+            LexerErrorValue::LeadingZero => "number has a leading zero",
+            LexerErrorValue::NumberOverflow => "number does not fit",
+            LexerErrorValue::StrayBackslash => "stray backslash",
+            LexerErrorValue::UnterminatedComment => "unterminated block comment",
+            LexerErrorValue::NumberTooLong => "number has too many digits",
+        }
+    }
+
+    // Codes are stable once published: a new variant takes the next free
+    // number and an existing code is never reassigned.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerErrorValue::UnrecognizedToken => "E0001",
+            LexerErrorValue::TooManyTokens => "E0002",
+            LexerErrorValue::LeadingZero => "E0003",
+            LexerErrorValue::NumberOverflow => "E0004",
+            LexerErrorValue::StrayBackslash => "E0005",
+            LexerErrorValue::UnterminatedComment => "E0006",
+            LexerErrorValue::NumberTooLong => "E0007",
+        }
+    }
+
+    fn json_value(&self) -> &'static str {
+        match self {
+            LexerErrorValue::UnrecognizedToken => "unrecognized_token",
+            LexerErrorValue::TooManyTokens => "too_many_tokens",
+            LexerErrorValue::LeadingZero => "leading_zero",
+            LexerErrorValue::NumberOverflow => "number_overflow",
+            LexerErrorValue::StrayBackslash => "stray_backslash",
+            LexerErrorValue::UnterminatedComment => "unterminated_comment",
+            LexerErrorValue::NumberTooLong => "number_too_long",
+        }
+    }
+}
+
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    let entries: Vec<String> = tokens.iter()
+        .map(|tok| format!(
+            "{{\"kind\":\"{}\",\"value\":{},\"line\":{},\"column\":{}}}",
+            tok.value.json_kind(), tok.value.json_value(), tok.line, tok.column
+        ))
+        .collect();
+    return format!("[{}]", entries.join(","));
+}
+
+pub fn error_to_json(err: &LexerError) -> String {
+    return format!(
+        "{{\"kind\":\"error\",\"value\":\"{}\",\"line\":{},\"column\":{}}}",
+        err.value.json_value(), err.line, err.column
+    );
+}
+
+fn summarize_error(e: &LexerError) -> String {
+    match e.suggestion {
+        Some(suggestion) => format!(
+            "error at line {} col {}: {} (did you mean `{}`?)",
+            e.line, e.column, e.value.message(), suggestion
+        ),
+        None => format!("error at line {} col {}: {}", e.line, e.column, e.value.message()),
+    }
+}
+
+pub fn summarize(result: &LexerResult<TokenStream>) -> String {
+    match result {
+        Ok(tokens) => format!("lexed {} tokens", tokens.len()),
+        Err(e) => summarize_error(e),
+    }
+}
+
+// Shows the offending source line under the summary with a caret at the
+// error column. Lines longer than `max_context_width` are cut down to a
+// window around the caret, with `...` marking each side that was cut.
+// Positions are read as if `err` came from lexing all of `input` with
+// default options; a position before line 1 shows an empty source line.
+pub fn render_error(input: &[u8], err: &LexerError, max_context_width: usize) -> String {
+    let source = err.line.checked_sub(1)
+        .and_then(|n| input.split(|b| *b == b'\n').nth(n))
+        .unwrap_or(&[]);
+    let chars: Vec<char> = String::from_utf8_lossy(source).chars().collect();
+    let caret = err.column.saturating_sub(1).min(chars.len());
+    let (start, end) = if chars.len() <= max_context_width {
+        (0, chars.len())
+    } else {
+        let start = caret.saturating_sub(max_context_width / 2)
+            .min(chars.len() - max_context_width);
+        (start, start + max_context_width)
+    };
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < chars.len() { "..." } else { "" };
+    let window: String = chars[start..end].iter().collect();
+    return format!(
+        "{}\n{}{}{}\n{}^",
+        summarize_error(err), prefix, window, suffix,
+        " ".repeat(prefix.len() + caret - start)
+    );
+}
+
+pub fn result_to_json(res: &LexerResult<TokenStream>) -> String {
+    match res {
+        Ok(tokens) => tokens_to_json(tokens),
+        Err(e) => error_to_json(e),
+    }
+}
+
 pub fn hello() {
     println!("Hello world")
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(input: &str) -> Vec<TokenValue> {
+        return values(&Lexer::from_cstream(input.as_bytes()).execute().unwrap());
+    }
+
+    #[test]
+    fn number_width_i64_fits_past_i32() {
+        let tokens = LexerBuilder::new()
+            .number_width(NumberWidth::I64)
+            .build(b"3000000000")
+            .execute()
+            .unwrap();
+        assert_eq!(values(&tokens), vec![TokenValue::Number64(3_000_000_000)]);
+        let err = Lexer::from_cstream(b"3000000000").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::NumberOverflow));
+    }
+
+    #[test]
+    fn non_ascii_digits_are_not_numbers() {
+        let err = Lexer::from_cstream("\u{0663}".as_bytes()).execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::UnrecognizedToken));
+        let long = "\u{0663}".repeat(20);
+        assert!(Lexer::from_cstream(long.as_bytes()).execute().is_err());
+    }
+
+    #[test]
+    fn max_number_digits_rejects_long_literals() {
+        let digits = "1".repeat(100);
+        let err = LexerBuilder::new()
+            .max_number_digits(10)
+            .build(digits.as_bytes())
+            .execute()
+            .unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::NumberTooLong));
+        assert_eq!((err.line, err.column), (1, 1));
+        let long = "\u{0663}".repeat(20);
+        let err = LexerBuilder::new()
+            .max_number_digits(3)
+            .build(long.as_bytes())
+            .execute()
+            .unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::UnrecognizedToken));
+    }
+
+    fn brackets(input: &str) -> Result<(), BracketError> {
+        return check_brackets(&Lexer::from_cstream(input.as_bytes()).execute().unwrap());
+    }
+
+    #[test]
+    fn check_brackets_balanced() {
+        assert!(brackets("(1 + 2)").is_ok());
+        assert!(brackets("{(1 + 2) * 3}").is_ok());
+        assert!(brackets("(!True)").is_ok());
+    }
+
+    #[test]
+    fn check_brackets_unclosed() {
+        let err = brackets("(1 + 2").unwrap_err();
+        assert_eq!(err.value(), BracketErrorValue::Unclosed);
+        assert_eq!((err.line(), err.column()), (1, 1));
+    }
+
+    #[test]
+    fn check_brackets_unexpected_close() {
+        let err = brackets("1 + 2)").unwrap_err();
+        assert_eq!(err.value(), BracketErrorValue::UnexpectedClose);
+        assert_eq!((err.line(), err.column()), (1, 6));
+        assert_eq!(brackets("(1}").unwrap_err().value(), BracketErrorValue::Mismatched);
+    }
+
+    #[test]
+    fn excl_lexes_as_excl() {
+        assert_eq!(lex("!True"), vec![TokenValue::Excl, TokenValue::Boolean(true.into())]);
+    }
+
+    #[test]
+    fn is_complete_expression_complete() {
+        assert_eq!(is_complete_expression("1 + 2"), Completeness::Complete);
+        assert_eq!(is_complete_expression("!True"), Completeness::Complete);
+    }
+
+    #[test]
+    fn is_complete_expression_incomplete() {
+        assert_eq!(is_complete_expression("(1 +"), Completeness::Incomplete);
+        assert_eq!(is_complete_expression("1 *"), Completeness::Incomplete);
+        assert_eq!(is_complete_expression(""), Completeness::Incomplete);
+    }
+
+    #[test]
+    fn is_complete_expression_invalid() {
+        assert_eq!(is_complete_expression("1 @"), Completeness::Invalid);
+        assert_eq!(is_complete_expression("1 ;"), Completeness::Invalid);
+        assert_eq!(is_complete_expression("#"), Completeness::Invalid);
+        assert_eq!(is_complete_expression("5 |> abs"), Completeness::Invalid);
+        assert_eq!(is_complete_expression("1 $"), Completeness::Invalid);
+        assert_eq!(is_complete_expression("1 + 2)"), Completeness::Invalid);
+    }
+
+    fn spacing(input: &str) -> Vec<Range<usize>> {
+        return check_operator_spacing(&Lexer::from_cstream(input.as_bytes()).execute().unwrap());
+    }
+
+    #[test]
+    fn check_operator_spacing_flags_unspaced() {
+        assert_eq!(spacing("1+2"), vec![1..2]);
+        assert_eq!(spacing("(1 +!True)"), vec![3..4]);
+    }
+
+    #[test]
+    fn check_operator_spacing_accepts_spaced_and_unary() {
+        assert!(spacing("1 + 2").is_empty());
+        assert!(spacing("(1 + !True)").is_empty());
+        assert!(spacing("-1 * (-2)").is_empty());
+    }
+
+    #[test]
+    fn numbers_and_sum() {
+        let tokens = Lexer::from_cstream(b"1 + 2 + 3").execute().unwrap();
+        assert_eq!(numbers(&tokens), vec![1, 2, 3]);
+        assert_eq!(sum_numbers(&tokens), 6);
+    }
+
+    #[test]
+    fn sum_numbers_counts_number64() {
+        let tokens = LexerBuilder::new()
+            .number_width(NumberWidth::I64)
+            .build(b"1 + 2")
+            .execute()
+            .unwrap();
+        assert!(numbers(&tokens).is_empty());
+        assert_eq!(sum_numbers(&tokens), 3);
+    }
+
+    #[test]
+    fn token_stream_len_and_significant() {
+        let tokens = Lexer::from_cstream(b"1 + 2").execute().unwrap();
+        assert_eq!(tokens.len(), 5);
+        assert!(!tokens.is_empty());
+        let significant: Vec<TokenValue> = tokens.significant().map(|tok| tok.value.clone()).collect();
+        assert_eq!(significant, vec![TokenValue::Number(1), TokenValue::Cross, TokenValue::Number(2)]);
+    }
+
+    #[test]
+    fn token_stream_first_error_free() {
+        let tokens = LexerBuilder::new()
+            .on_unknown(OnUnknown::Replace)
+            .build(b"1 $ 2")
+            .execute()
+            .unwrap();
+        assert_eq!(values(tokens.first_error_free()), vec![TokenValue::Number(1), TokenValue::Whitespace]);
+        let tokens = Lexer::from_cstream(b"1 + 2").execute().unwrap();
+        assert_eq!(tokens.first_error_free().len(), 5);
+    }
+
+    #[test]
+    fn false_as_last_token_of_multiline_input() {
+        let tokens = Lexer::from_cstream(b"1 +\n  2 *\n\t(3) && False").execute().unwrap();
+        let last = tokens.last().unwrap();
+        assert_eq!(last.value, TokenValue::Boolean(false.into()));
+        assert_eq!((last.line, last.column), (3, 9));
+    }
+
+    #[test]
+    fn empty_input_is_immediate_eof() {
+        assert!(Lexer::from_cstream(b"").execute().unwrap().is_empty());
+        let mut lexer = Lexer::from_cstream(b"");
+        assert!(lexer.lex_one().unwrap().is_none());
+        assert!(lexer.lex_one().unwrap().is_none());
+        assert!(Lexer::from_cstream(b"").next().is_none());
+        assert!(Lexer::from_cstream(b"").peek_n(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn booleans_are_keywords_and_numbers_literals() {
+        assert_eq!(TokenValue::Boolean(true.into()).category(), TokenCategory::Keyword);
+        assert!(TokenValue::Boolean(true.into()).is_keyword());
+        assert_eq!(TokenValue::Number(1).category(), TokenCategory::Literal);
+        assert!(!TokenValue::Number(1).is_keyword());
+    }
+
+    #[test]
+    fn render_error_truncates_long_lines() {
+        let mut line = "1 + ".repeat(50);
+        line.replace_range(100..101, "$");
+        assert_eq!(line.len(), 200);
+        let err = Lexer::from_cstream(line.as_bytes()).execute().unwrap_err();
+        let rendered = render_error(line.as_bytes(), &err, 40);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "error at line 1 col 101: unrecognized token");
+        assert!(lines[1].starts_with("...") && lines[1].ends_with("..."));
+        assert_eq!(lines[1].len(), 46);
+        let caret = lines[2].find('^').unwrap();
+        assert_eq!(lines[2].len(), caret + 1);
+        assert_eq!(&lines[1][caret..caret + 1], "$");
+    }
+
+    #[test]
+    fn render_error_short_line_and_zero_offset() {
+        let err = Lexer::from_cstream(b"1 $").execute().unwrap_err();
+        assert_eq!(render_error(b"1 $", &err, 40), "error at line 1 col 3: unrecognized token\n1 $\n  ^");
+        let err = Lexer::with_line_offset(b"$", 0, 0).execute().unwrap_err();
+        assert_eq!(render_error(b"$", &err, 40), "error at line 0 col 0: unrecognized token\n\n^");
+    }
+
+    #[test]
+    fn radix_prefix_dollar_hex() {
+        let tokens = LexerBuilder::new()
+            .radix_prefix("$", 16)
+            .build(b"$FF")
+            .execute()
+            .unwrap();
+        assert_eq!(values(&tokens), vec![TokenValue::Number(255)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "radix prefix must not be empty")]
+    fn radix_prefix_rejects_empty() {
+        let _ = LexerBuilder::new().radix_prefix("", 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "radix prefix must not start with a non-zero digit")]
+    fn radix_prefix_rejects_leading_digit() {
+        let _ = LexerBuilder::new().radix_prefix("1x", 16);
+    }
+
+    #[test]
+    fn longest_valid_prefix_stops_at_error() {
+        let (tokens, offset) = longest_valid_prefix(b"1 + \\ 2");
+        assert_eq!(values(&tokens), vec![
+            TokenValue::Number(1), TokenValue::Whitespace, TokenValue::Cross, TokenValue::Whitespace,
+        ]);
+        assert_eq!(offset, 4);
+        let (tokens, offset) = longest_valid_prefix(b"1 + @ 2");
+        assert_eq!((tokens.len(), offset), (7, 7));
+    }
+
+    #[test]
+    fn suggestions_for_lookalike_operators() {
+        let err = Lexer::from_cstream(b"1 =< 2").execute().unwrap_err();
+        assert_eq!(err.suggestion(), Some("<="));
+        let err = Lexer::from_cstream("2 \u{00d7} 3".as_bytes()).execute().unwrap_err();
+        assert_eq!(err.suggestion(), Some("*"));
+        assert_eq!(Lexer::from_cstream(b"$").execute().unwrap_err().suggestion(), None);
+    }
+
+    #[test]
+    fn builder_options_take_effect() {
+        let tokens = LexerBuilder::new()
+            .bool_style(BoolStyle::Lowercase)
+            .emit_eof(true)
+            .build(b"true")
+            .execute()
+            .unwrap();
+        assert_eq!(values(&tokens), vec![
+            TokenValue::Boolean(BooleanRepr::new(true, "true")), TokenValue::Eof,
+        ]);
+    }
+
+    #[test]
+    fn token_kinds_deduplicate_in_hash_set() {
+        let tokens = Lexer::from_cstream(b"1 + 2 + 3").execute().unwrap();
+        let kinds: std::collections::HashSet<TokenKind> = tokens.iter().map(|tok| tok.kind()).collect();
+        assert_eq!(kinds.len(), 3);
+        assert!(kinds.contains(&TokenKind::Number) && kinds.contains(&TokenKind::Cross));
+        let values: std::collections::HashSet<TokenValue> = values(&tokens).into_iter().collect();
+        assert_eq!(values.len(), 5);
+    }
+
+    #[test]
+    fn token_kind_orders_by_declaration() {
+        assert!(TokenKind::Number < TokenKind::Cross);
+        assert!(TokenKind::Cross < TokenKind::Dash);
+        assert!(TokenKind::Star < TokenKind::Slash);
+        assert!(TokenKind::Excl < TokenKind::Tilde);
+        let mut kinds = vec![TokenKind::Slash, TokenKind::Cross, TokenKind::Star, TokenKind::Dash];
+        kinds.sort();
+        assert_eq!(kinds, vec![TokenKind::Cross, TokenKind::Dash, TokenKind::Star, TokenKind::Slash]);
+        assert_eq!(kinds.binary_search(&TokenKind::Star), Ok(2));
+    }
+
+    #[test]
+    fn group_by_line_three_lines() {
+        let tokens = Lexer::from_cstream(b"1 +\n2\n* 3").execute().unwrap();
+        let groups: Vec<(usize, Vec<TokenValue>)> = group_by_line(&tokens).into_iter()
+            .map(|(line, group)| (line, group.into_iter().map(|tok| tok.value.clone()).collect()))
+            .collect();
+        assert_eq!(groups, vec![
+            (1, vec![TokenValue::Number(1), TokenValue::Whitespace, TokenValue::Cross, TokenValue::Whitespace]),
+            (2, vec![TokenValue::Number(2), TokenValue::Whitespace]),
+            (3, vec![TokenValue::Star, TokenValue::Whitespace, TokenValue::Number(3)]),
+        ]);
+    }
+
+    #[test]
+    fn tab_width_counts_tab_columns() {
+        let tokens = LexerBuilder::new().tab_width(4).build(b"\t+").execute().unwrap();
+        assert_eq!((tokens[1].value.clone(), tokens[1].column), (TokenValue::Cross, 5));
+        let tokens = Lexer::from_cstream(b"\t+").execute().unwrap();
+        assert_eq!(tokens[1].column, 2);
+    }
+
+    #[test]
+    fn tokens_to_json_small_stream() {
+        let tokens = Lexer::from_cstream(b"1+True").execute().unwrap();
+        assert_eq!(tokens_to_json(&tokens), concat!(
+            "[{\"kind\":\"number\",\"value\":1,\"line\":1,\"column\":1},",
+            "{\"kind\":\"cross\",\"value\":null,\"line\":1,\"column\":2},",
+            "{\"kind\":\"boolean\",\"value\":true,\"line\":1,\"column\":3}]",
+        ));
+        assert_eq!(tokens_to_json(&[]), "[]");
+    }
+
+    #[test]
+    fn error_to_json_has_error_kind() {
+        let err = Lexer::from_cstream(b"1 $").execute().unwrap_err();
+        assert_eq!(
+            error_to_json(&err),
+            "{\"kind\":\"error\",\"value\":\"unrecognized_token\",\"line\":1,\"column\":3}"
+        );
+    }
+
+    #[test]
+    fn lex_one_matches_execute() {
+        let input = b"(11 + 12)\n* False - 123 {} || && ==";
+        let mut lexer = Lexer::from_cstream(input);
+        let mut tokens: Vec<Token> = Vec::new();
+        while let Some(tok) = lexer.lex_one().unwrap() {
+            tokens.push(tok);
+        }
+        assert_eq!(tokens, Lexer::from_cstream(input).execute().unwrap().into_vec());
+        assert!(lexer.lex_one().unwrap().is_none());
+    }
+
+    #[test]
+    fn underscore_and_identifiers() {
+        assert_eq!(lex("_"), vec![TokenValue::Underscore]);
+        assert_eq!(lex("_x"), vec![TokenValue::Identifier(String::from("_x"))]);
+        assert_eq!(lex("x_"), vec![TokenValue::Identifier(String::from("x_"))]);
+    }
+
+    #[test]
+    fn is_valid_inputs() {
+        assert!(is_valid(b"(1 + 2) * True"));
+        assert!(is_valid(b""));
+        assert!(!is_valid(b"1 $ 2"));
+    }
+
+    #[test]
+    fn tokenize_lines_isolates_bad_line() {
+        let results = tokenize_lines("1 + 2\n3 $ 4\nTrue");
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().len(), 5);
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!((err.line, err.column), (1, 3));
+        let last = results[2].as_ref().unwrap();
+        assert_eq!((last[0].line, last[0].column), (1, 1));
+    }
+
+    #[test]
+    fn whitespace_map_mixed_input() {
+        assert_eq!(whitespace_map(b"1  + $\n\t2"), vec![(1, 2, 2), (1, 5, 1), (1, 7, 2)]);
+        assert!(whitespace_map(b"1+2").is_empty());
+    }
+
+    #[test]
+    fn bool_style_both_spellings() {
+        for (spelling, value) in [("true", true), ("True", true), ("false", false), ("False", false)] {
+            let tokens = LexerBuilder::new()
+                .bool_style(BoolStyle::Both)
+                .build(spelling.as_bytes())
+                .execute()
+                .unwrap();
+            assert_eq!(values(&tokens), vec![TokenValue::Boolean(BooleanRepr::new(value, spelling))]);
+        }
+        let tokens = LexerBuilder::new()
+            .bool_style(BoolStyle::Both)
+            .build(b"truely")
+            .execute()
+            .unwrap();
+        assert_eq!(values(&tokens), vec![TokenValue::Identifier(String::from("truely"))]);
+    }
+
+    #[test]
+    fn values_ignore_whitespace_layout() {
+        let significant = |input: &[u8]| -> Vec<TokenValue> {
+            let tokens = Lexer::from_cstream(input).execute().unwrap();
+            return values(&tokens).into_iter().filter(|v| *v != TokenValue::Whitespace).collect();
+        };
+        assert_eq!(significant(b"(1+2) * 3"), significant(b"  ( 1 +\n2 )*\t3 "));
+    }
+
+    #[test]
+    fn interned_identifiers_share_ids() {
+        let mut lexer = LexerBuilder::new().intern_identifiers(true).build(b"foo + bar + foo");
+        let tokens = lexer.execute().unwrap();
+        let ids: Vec<u32> = tokens.iter()
+            .filter_map(|tok| match tok.value {
+                TokenValue::Symbol(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0], ids[2]);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(lexer.resolve(ids[0]), "foo");
+        assert_eq!(lexer.resolve(ids[1]), "bar");
+    }
+
+    #[test]
+    fn validate_operators_reports_bad_cluster() {
+        assert_eq!(validate_operators(b"1 =+ 2"), vec![(1, 3, b"=+".to_vec())]);
+        assert!(validate_operators(b"1 == 2").is_empty());
+    }
+
+    #[test]
+    fn emit_eof_only_when_enabled() {
+        let tokens = Lexer::from_cstream(b"1 +\n22").execute().unwrap();
+        assert!(tokens.iter().all(|tok| tok.value != TokenValue::Eof));
+        let tokens = LexerBuilder::new().emit_eof(true).build(b"1 +\n22").execute().unwrap();
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.value, TokenValue::Eof);
+        assert_eq!((eof.line, eof.column, eof.span.clone()), (2, 3, 6..6));
+    }
+
+    #[test]
+    fn max_tokens_errors_past_limit() {
+        let err = LexerBuilder::new().max_tokens(3).build(b"1 + 2").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::TooManyTokens));
+        assert_eq!((err.line, err.column), (1, 4));
+        assert_eq!(LexerBuilder::new().max_tokens(3).build(b"1 +").execute().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn describe_names() {
+        assert_eq!(TokenValue::Cross.describe(), "plus sign");
+        assert_eq!(TokenValue::Number(5).describe(), "number");
+        assert_eq!(TokenValue::OpenRoundBracket.describe(), "open parenthesis");
+        assert_eq!(TokenValue::Boolean(true.into()).describe(), "boolean literal");
+    }
+
+    #[test]
+    fn with_index_finds_token_at_offset() {
+        let (tokens, index) = with_index(b"11 + 22").unwrap();
+        let at = |offset: usize| -> &TokenValue {
+            let i = index.partition_point(|(start, _)| *start <= offset) - 1;
+            return &tokens[index[i].1].value;
+        };
+        assert_eq!(at(1), &TokenValue::Number(11));
+        assert_eq!(at(3), &TokenValue::Cross);
+        assert_eq!(at(6), &TokenValue::Number(22));
+    }
+
+    #[test]
+    fn strict_numbers_leading_zero() {
+        let strict = |input: &[u8]| LexerBuilder::new().strict_numbers(true).build(input).execute();
+        assert!(matches!(strict(b"007").unwrap_err().value, LexerErrorValue::LeadingZero));
+        assert_eq!(values(&strict(b"0").unwrap()), vec![TokenValue::Number(0)]);
+        assert_eq!(values(&strict(b"0xFF").unwrap()), vec![TokenValue::Number(255)]);
+    }
+
+    #[test]
+    fn partial_and_extended_true() {
+        assert_eq!(lex("Tru"), vec![TokenValue::Identifier(String::from("Tru"))]);
+        assert_eq!(lex("True"), vec![TokenValue::Boolean(true.into())]);
+        assert_eq!(lex("Truex"), vec![TokenValue::Identifier(String::from("Truex"))]);
+    }
+
+    #[test]
+    fn alias_and_lexes_as_double_and() {
+        let tokens = LexerBuilder::new()
+            .alias("and", TokenValue::DoubleAnd)
+            .build(b"True and False")
+            .execute()
+            .unwrap();
+        let significant: Vec<TokenValue> = tokens.significant().map(|tok| tok.value.clone()).collect();
+        assert_eq!(significant, vec![
+            TokenValue::Boolean(true.into()), TokenValue::DoubleAnd, TokenValue::Boolean(false.into()),
+        ]);
+    }
+
+    #[test]
+    fn stray_backslash_error() {
+        let err = Lexer::from_cstream(b"1 \\ 2").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::StrayBackslash));
+        assert_eq!((err.line, err.column), (1, 3));
+    }
+
+    fn lex_with_comments(style: CommentStyle, input: &str) -> Vec<TokenValue> {
+        let tokens = LexerBuilder::new().comment_style(style).build(input.as_bytes()).execute().unwrap();
+        return values(&tokens);
+    }
+
+    #[test]
+    fn comment_styles_skip_to_newline() {
+        let expected = vec![
+            TokenValue::Number(1), TokenValue::Whitespace, TokenValue::Whitespace, TokenValue::Number(2),
+        ];
+        assert_eq!(lex_with_comments(CommentStyle::DoubleSlash, "1 // one\n2"), expected);
+        assert_eq!(lex_with_comments(CommentStyle::Hash, "1 # one\n2"), expected);
+        assert_eq!(lex_with_comments(CommentStyle::DoubleDash, "1 -- one\n2"), expected);
+    }
+
+    #[test]
+    fn single_slash_and_dash_are_operators_with_comments_on() {
+        assert_eq!(lex_with_comments(CommentStyle::DoubleSlash, "1/2"), vec![
+            TokenValue::Number(1), TokenValue::Slash, TokenValue::Number(2),
+        ]);
+        assert_eq!(lex_with_comments(CommentStyle::DoubleDash, "1-2"), vec![
+            TokenValue::Number(1), TokenValue::Dash, TokenValue::Number(2),
+        ]);
+    }
+
+    #[test]
+    fn value_eq_ignores_position() {
+        let tokens = Lexer::from_cstream(b"1 + 1").execute().unwrap();
+        assert!(tokens[0].value_eq(&tokens[4]));
+        assert_ne!(tokens[0], tokens[4]);
+        assert!(!tokens[0].value_eq(&tokens[2]));
+    }
+
+    #[test]
+    fn on_unknown_replace_keeps_lexing() {
+        let tokens = LexerBuilder::new()
+            .on_unknown(OnUnknown::Replace)
+            .build(b"1 $ 2")
+            .execute()
+            .unwrap();
+        assert_eq!(values(&tokens), vec![
+            TokenValue::Number(1), TokenValue::Whitespace, TokenValue::Unknown(b'$'),
+            TokenValue::Whitespace, TokenValue::Number(2),
+        ]);
+    }
+
+    fn lex_block(input: &[u8]) -> LexerResult<TokenStream> {
+        return LexerBuilder::new().comment_style(CommentStyle::Block).build(input).execute();
+    }
+
+    #[test]
+    fn block_comment_single_line() {
+        assert_eq!(values(&lex_block(b"1/* one */+2").unwrap()), vec![
+            TokenValue::Number(1), TokenValue::Cross, TokenValue::Number(2),
+        ]);
+    }
+
+    #[test]
+    fn block_comment_multi_line() {
+        let tokens = lex_block(b"1 /* one\ntwo\n */ 2").unwrap();
+        let last = tokens.last().unwrap();
+        assert_eq!(last.value, TokenValue::Number(2));
+        assert_eq!((last.line, last.column), (3, 5));
+    }
+
+    #[test]
+    fn block_comment_unterminated() {
+        let err = lex_block(b"1 /* one\n").unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::UnterminatedComment));
+        assert_eq!((err.line, err.column), (1, 3));
+    }
+
+    #[test]
+    fn with_line_offset_reports_offset_position() {
+        let err = Lexer::with_line_offset(b"$", 10, 5).execute().unwrap_err();
+        assert_eq!((err.line, err.column), (10, 5));
+        let err = Lexer::with_line_offset(b"1\n $", 10, 5).execute().unwrap_err();
+        assert_eq!((err.line, err.column), (11, 2));
+    }
+
+    #[test]
+    fn reconstruct_round_trips() {
+        let input = b"(11 + 12) * False";
+        let tokens = Lexer::from_cstream(input).execute().unwrap();
+        assert_eq!(reconstruct(input, &tokens), input.to_vec());
+    }
+
+    #[test]
+    fn tokens_in_range_includes_clipped_tokens() {
+        let tokens = Lexer::from_cstream(b"1 + 22 + 3").execute().unwrap();
+        let found: Vec<TokenValue> = tokens_in_range(&tokens, 5..8).into_iter().map(|tok| tok.value.clone()).collect();
+        assert_eq!(found, vec![TokenValue::Number(22), TokenValue::Whitespace, TokenValue::Cross]);
+    }
+
+    #[test]
+    fn cloned_lexer_is_independent() {
+        let mut lexer = Lexer::from_cstream(b"1 + 2");
+        let mut clone = lexer.clone();
+        assert_eq!(clone.lex_one().unwrap().unwrap().value, TokenValue::Number(1));
+        assert_eq!(clone.lex_one().unwrap().unwrap().value, TokenValue::Whitespace);
+        assert_eq!(lexer.lex_one().unwrap().unwrap().value, TokenValue::Number(1));
+        assert_eq!(lexer.execute().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn keyword_table_lookup() {
+        assert_eq!(keyword("True", BoolStyle::Capitalized), Some(TokenValue::Boolean(true.into())));
+        assert_eq!(keyword("foo", BoolStyle::Capitalized), None);
+        assert_eq!(keyword("true", BoolStyle::Capitalized), None);
+    }
+
+    #[test]
+    fn execute_prefix_first_two() {
+        let mut lexer = Lexer::from_cstream(b"1 + 2 + 3");
+        let prefix = lexer.execute_prefix(2).unwrap();
+        assert_eq!(values(&prefix), vec![TokenValue::Number(1), TokenValue::Whitespace]);
+        assert_eq!(lexer.lex_one().unwrap().unwrap().value, TokenValue::Cross);
+    }
+
+    #[test]
+    fn hex_wraps_reinterprets_unsigned() {
+        let wrap = |input: &[u8]| LexerBuilder::new().hex_wraps(true).build(input).execute().unwrap();
+        assert_eq!(values(&wrap(b"0xFFFFFFFF")), vec![TokenValue::Number(-1)]);
+        assert_eq!(values(&wrap(b"0x80000000")), vec![TokenValue::Number(i32::MIN)]);
+        let err = Lexer::from_cstream(b"0xFFFFFFFF").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::NumberOverflow));
+    }
+
+    #[test]
+    fn annotate_pairs_values_with_source() {
+        let pairs = annotate("True + 12").unwrap();
+        assert_eq!(pairs[0], (TokenValue::Boolean(true.into()), String::from("True")));
+        assert_eq!(pairs[4], (TokenValue::Number(12), String::from("12")));
+        assert_eq!(pairs[1], (TokenValue::Whitespace, String::from(" ")));
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let input = "\u{03b1}\u{03b2} + 1";
+        let tokens = LexerBuilder::new()
+            .identifier_chars(IdentClass::Unicode)
+            .build(input.as_bytes())
+            .execute()
+            .unwrap();
+        assert_eq!(tokens[0].value, TokenValue::Identifier(String::from("\u{03b1}\u{03b2}")));
+        assert_eq!((tokens[2].value.clone(), tokens[2].column), (TokenValue::Cross, 4));
+        assert!(Lexer::from_cstream(input.as_bytes()).execute().is_err());
+    }
+
+    #[test]
+    fn debug_positions_table() {
+        let tokens = Lexer::from_cstream(b"1+2").execute().unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        debug_positions(&tokens, &mut buf).unwrap();
+        let table = String::from_utf8(buf).unwrap();
+        let rows: Vec<&str> = table.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], "token     | line | column | start | end");
+        assert_eq!(rows[1], "Number(1) | 1    | 1      | 0     | 1");
+    }
+
+    #[test]
+    fn source_len_variants() {
+        assert_eq!(TokenValue::Cross.source_len(), Some(1));
+        assert_eq!(TokenValue::DoubleEqual.source_len(), Some(2));
+        assert_eq!(TokenValue::Boolean(true.into()).source_len(), Some(4));
+        assert_eq!(TokenValue::Boolean(false.into()).source_len(), Some(5));
+        assert_eq!(TokenValue::Number(123).source_len(), Some(3));
+        assert_eq!(TokenValue::Number64(-45).source_len(), Some(3));
+        assert_eq!(TokenValue::Whitespace.source_len(), None);
+    }
+
+    #[test]
+    fn execute_into_reuses_buffer() {
+        let mut buf: Vec<Token> = Vec::new();
+        Lexer::from_cstream(b"(11 + 12) * 3").execute_into(&mut buf).unwrap();
+        assert_eq!(buf, Lexer::from_cstream(b"(11 + 12) * 3").execute().unwrap().into_vec());
+        let capacity = buf.capacity();
+        Lexer::from_cstream(b"1").execute_into(&mut buf).unwrap();
+        assert_eq!(values(&buf), vec![TokenValue::Number(1)]);
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn at_and_hash_without_comments() {
+        assert_eq!(lex("@x"), vec![TokenValue::At, TokenValue::Identifier(String::from("x"))]);
+        assert_eq!(lex("#1"), vec![TokenValue::Hash, TokenValue::Number(1)]);
+        let tokens = LexerBuilder::new().comment_style(CommentStyle::Hash).build(b"#1").execute().unwrap();
+        assert!(tokens.is_empty());
+    }
+
+    #[test]
+    fn highlight_category_all_variants() {
+        let expected = [
+            (TokenValue::Number(1), HighlightCategory::Number),
+            (TokenValue::Number64(1), HighlightCategory::Number),
+            (TokenValue::Boolean(true.into()), HighlightCategory::Boolean),
+            (TokenValue::Character(b'a'), HighlightCategory::Number),
+            (TokenValue::Cross, HighlightCategory::Operator),
+            (TokenValue::Dash, HighlightCategory::Operator),
+            (TokenValue::Star, HighlightCategory::Operator),
+            (TokenValue::Slash, HighlightCategory::Operator),
+            (TokenValue::Whitespace, HighlightCategory::Whitespace),
+            (TokenValue::OpenRoundBracket, HighlightCategory::Bracket),
+            (TokenValue::CloseRoundBracket, HighlightCategory::Bracket),
+            (TokenValue::OpenCurlyBracket, HighlightCategory::Bracket),
+            (TokenValue::CloseCurlyBracket, HighlightCategory::Bracket),
+            (TokenValue::Equal, HighlightCategory::Operator),
+            (TokenValue::ExclEqual, HighlightCategory::Operator),
+            (TokenValue::DoubleEqual, HighlightCategory::Operator),
+            (TokenValue::DoubleAnd, HighlightCategory::Operator),
+            (TokenValue::DoublePipe, HighlightCategory::Operator),
+            (TokenValue::Excl, HighlightCategory::Operator),
+            (TokenValue::Identifier(String::from("x")), HighlightCategory::Identifier),
+            (TokenValue::Symbol(0), HighlightCategory::Identifier),
+            (TokenValue::Underscore, HighlightCategory::Identifier),
+            (TokenValue::Eof, HighlightCategory::Whitespace),
+            (TokenValue::Unknown(b'$'), HighlightCategory::Error),
+            (TokenValue::At, HighlightCategory::Operator),
+            (TokenValue::Hash, HighlightCategory::Operator),
+            (TokenValue::Semicolon, HighlightCategory::Operator),
+            (TokenValue::PipeForward, HighlightCategory::Operator),
+            (TokenValue::Tilde, HighlightCategory::Operator),
+        ];
+        for (value, category) in expected {
+            assert_eq!(highlight_category(&value), category, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn highlight_ranges() {
+        let ranges = LexerBuilder::new()
+            .comment_style(CommentStyle::Block)
+            .build(b"1 /* c */+x")
+            .highlight();
+        assert_eq!(ranges, vec![
+            (HighlightCategory::Number, 0, 1),
+            (HighlightCategory::Whitespace, 1, 2),
+            (HighlightCategory::Comment, 2, 9),
+            (HighlightCategory::Operator, 9, 10),
+            (HighlightCategory::Identifier, 10, 11),
+        ]);
+        assert_eq!(Lexer::from_cstream(b"1 $").highlight(), vec![
+            (HighlightCategory::Number, 0, 1),
+            (HighlightCategory::Whitespace, 1, 2),
+            (HighlightCategory::Error, 2, 3),
+        ]);
+    }
+
+    #[test]
+    fn lines_with_tokens_three_lines() {
+        let lines: Vec<(usize, LexerResult<Vec<Token>>)> = Lexer::lines_with_tokens(b"1 + 2\n3 $\nTrue").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].0, 1);
+        assert_eq!(lines[0].1.as_ref().unwrap().len(), 5);
+        let err = lines[1].1.as_ref().unwrap_err();
+        assert_eq!((lines[1].0, err.line, err.column), (2, 2, 3));
+        let last = lines[2].1.as_ref().unwrap();
+        assert_eq!((lines[2].0, last[0].line), (3, 3));
+    }
+
+    #[test]
+    fn is_any_kind_membership() {
+        let tokens = Lexer::from_cstream(b"+").execute().unwrap();
+        assert!(tokens[0].is_any(&[TokenKind::Cross, TokenKind::Dash]));
+        assert!(!tokens[0].is_any(&[TokenKind::Star]));
+        assert!(!tokens[0].is_any(&[]));
+    }
+
+    #[test]
+    fn minify_strips_whitespace() {
+        assert_eq!(minify("( 11  +  12 )").unwrap(), "(11+12)");
+        assert_eq!(minify("1 2").unwrap(), "1 2");
+        assert_eq!(minify("True  x").unwrap(), "True x");
+        assert_eq!(minify("= =").unwrap(), "= =");
+    }
+
+    #[test]
+    fn error_codes_per_variant() {
+        assert_eq!(LexerErrorValue::UnrecognizedToken.code(), "E0001");
+        assert_eq!(LexerErrorValue::TooManyTokens.code(), "E0002");
+        assert_eq!(LexerErrorValue::LeadingZero.code(), "E0003");
+        assert_eq!(LexerErrorValue::NumberOverflow.code(), "E0004");
+        assert_eq!(LexerErrorValue::StrayBackslash.code(), "E0005");
+        assert_eq!(LexerErrorValue::UnterminatedComment.code(), "E0006");
+        assert_eq!(LexerErrorValue::NumberTooLong.code(), "E0007");
+    }
+
+    #[test]
+    fn peek_n_does_not_advance() {
+        let mut lexer = Lexer::from_cstream(b"1 + 2");
+        let peeked = lexer.peek_n(3).unwrap();
+        assert_eq!(values(&peeked), vec![TokenValue::Number(1), TokenValue::Whitespace, TokenValue::Cross]);
+        assert_eq!(lexer.next().unwrap().unwrap(), peeked[0]);
+    }
+
+    #[test]
+    fn boolean_spelling_round_trips() {
+        let tokens = LexerBuilder::new().bool_style(BoolStyle::Both).build(b"true").execute().unwrap();
+        assert_eq!(tokens[0].value.to_string(), "true");
+        match &tokens[0].value {
+            TokenValue::Boolean(repr) => assert!(repr.value()),
+            other => panic!("expected a boolean, got {:?}", other),
+        };
+    }
+
+    #[test]
+    fn split_at_semicolons_groups() {
+        let split = |input: &[u8]| -> Vec<Vec<TokenValue>> {
+            let tokens = Lexer::from_cstream(input).execute().unwrap();
+            return split_at_semicolons(&tokens).into_iter().map(values).collect();
+        };
+        assert_eq!(split(b"1;2;3"), vec![
+            vec![TokenValue::Number(1)], vec![TokenValue::Number(2)], vec![TokenValue::Number(3)],
+        ]);
+        assert_eq!(split(b"1;2;"), vec![vec![TokenValue::Number(1)], vec![TokenValue::Number(2)]]);
+        assert_eq!(split(b";"), vec![Vec::<TokenValue>::new()]);
+        assert!(split(b"").is_empty());
+    }
+
+    #[test]
+    fn lex_events_break_after_two() {
+        let mut lexer = Lexer::from_cstream(b"1 + 2");
+        let mut seen: Vec<TokenValue> = Vec::new();
+        lexer.lex_events(|tok| {
+            seen.push(tok.value.clone());
+            if seen.len() == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        }).unwrap();
+        assert_eq!(seen, vec![TokenValue::Number(1), TokenValue::Whitespace]);
+        assert_eq!(lexer.lex_one().unwrap().unwrap().value, TokenValue::Cross);
+    }
+
+    #[test]
+    fn source_id_tags_tokens() {
+        let first = LexerBuilder::new().source_id(0).build(b"1 + 2").execute().unwrap();
+        let second = LexerBuilder::new().source_id(1).build(b"3").execute().unwrap();
+        assert!(first.iter().all(|tok| tok.source_id() == 0));
+        assert!(second.iter().all(|tok| tok.source_id() == 1));
+        assert_eq!(Lexer::from_cstream(b"3").execute().unwrap()[0].source_id(), 0);
+    }
+
+    #[test]
+    fn operators_used_distinct_kinds() {
+        let tokens = Lexer::from_cstream(b"1 + 2 * 3 - 4").execute().unwrap();
+        let used: Vec<TokenKind> = operators_used(&tokens).into_iter().collect();
+        assert_eq!(used, vec![TokenKind::Cross, TokenKind::Dash, TokenKind::Star]);
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            return Err(io::Error::other("boom"));
+        }
+    }
+
+    #[test]
+    fn tokenize_reader_in_memory() {
+        let tokens = tokenize_reader(&mut &b"1 + 2"[..]).unwrap().unwrap();
+        assert_eq!(tokens.len(), 5);
+        assert!(tokenize_reader(&mut &b"1 $"[..]).unwrap().is_err());
+        assert!(tokenize_reader(&mut FailingReader).is_err());
+    }
+
+    #[test]
+    fn case_insensitive_keywords_and_prefixes() {
+        let insensitive = |input: &[u8]| LexerBuilder::new().case_insensitive(true).build(input).execute().unwrap();
+        assert_eq!(values(&insensitive(b"TRUE")), vec![TokenValue::Boolean(BooleanRepr::new(true, "TRUE"))]);
+        assert_eq!(values(&insensitive(b"0XAb")), vec![TokenValue::Number(171)]);
+        assert_eq!(lex("TRUE"), vec![TokenValue::Identifier(String::from("TRUE"))]);
+    }
+
+    #[test]
+    fn empty_lexer_yields_nothing() {
+        assert!(Lexer::empty().execute().unwrap().is_empty());
+        assert!(Lexer::default().execute().unwrap().is_empty());
+    }
+
+    #[test]
+    fn token_at_positions() {
+        let tokens = Lexer::from_cstream(b"12 +\n  345").execute().unwrap();
+        assert_eq!(token_at(&tokens, 1, 2).unwrap().value, TokenValue::Number(12));
+        assert_eq!(token_at(&tokens, 2, 4).unwrap().value, TokenValue::Number(345));
+        assert_eq!(token_at(&tokens, 2, 1).unwrap().value, TokenValue::Whitespace);
+        assert_eq!(token_at(&tokens, 1, 3).unwrap().value, TokenValue::Whitespace);
+        assert!(token_at(&tokens, 2, 6).is_none());
+    }
+
+    #[test]
+    fn summarize_success_and_error() {
+        assert_eq!(summarize(&Lexer::from_cstream(b"1 + 2").execute()), "lexed 5 tokens");
+        assert_eq!(
+            summarize(&Lexer::from_cstream(b"1\n $").execute()),
+            "error at line 2 col 2: unrecognized token"
+        );
+        assert_eq!(
+            summarize(&Lexer::from_cstream(b"1 =< 2").execute()),
+            "error at line 1 col 4: unrecognized token (did you mean `<=`?)"
+        );
+    }
+
+    #[test]
+    fn pipe_forward_and_spaced_pipe() {
+        assert_eq!(lex("5 |> abs"), vec![
+            TokenValue::Number(5), TokenValue::Whitespace, TokenValue::PipeForward,
+            TokenValue::Whitespace, TokenValue::Identifier(String::from("abs")),
+        ]);
+        let err = Lexer::from_cstream(b"a | >b").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::UnrecognizedToken));
+        assert_eq!((err.column, err.suggestion), (3, Some("||")));
+    }
+
+    #[test]
+    fn tilde_lexes_as_tilde() {
+        assert_eq!(lex("~5"), vec![TokenValue::Tilde, TokenValue::Number(5)]);
+    }
+
+    #[test]
+    fn sum_numbers_does_not_overflow_i64() {
+        let sum = |input: &[u8]| -> i128 {
+            let tokens = LexerBuilder::new().number_width(NumberWidth::I64).build(input).execute().unwrap();
+            return sum_numbers(&tokens);
+        };
+        assert_eq!(sum(b"9223372036854775807 + 1"), i64::MAX as i128 + 1);
+        assert_eq!(sum(b"9223372036854775807 + 9223372036854775807"), 2 * i64::MAX as i128);
+    }
+
+    #[test]
+    fn max_tokens_counts_eof() {
+        let err = LexerBuilder::new().max_tokens(1).emit_eof(true).build(b"1").execute().unwrap_err();
+        assert!(matches!(err.value, LexerErrorValue::TooManyTokens));
+        assert_eq!((err.line, err.column), (1, 2));
+        let tokens = LexerBuilder::new().max_tokens(2).emit_eof(true).build(b"1").execute().unwrap();
+        assert_eq!(values(&tokens), vec![TokenValue::Number(1), TokenValue::Eof]);
+    }
+
+    #[test]
+    fn case_insensitive_boolean_keeps_spelling() {
+        let tokens = LexerBuilder::new().case_insensitive(true).build(b"tRuE").execute().unwrap();
+        assert_eq!(tokens[0].value.to_string(), "tRuE");
+        assert_eq!(tokens[0].value.source_len(), Some(4));
+        match &tokens[0].value {
+            TokenValue::Boolean(repr) => assert!(repr.value()),
+            other => panic!("expected a boolean, got {:?}", other),
+        };
+    }
+
+    #[test]
+    fn token_at_counts_tab_width() {
+        let tokens = LexerBuilder::new().tab_width(4).build(b"\t+").execute().unwrap();
+        for column in 1..5 {
+            assert_eq!(token_at(&tokens, 1, column).unwrap().value, TokenValue::Whitespace);
+        }
+        assert_eq!(token_at(&tokens, 1, 5).unwrap().value, TokenValue::Cross);
+        assert!(token_at(&tokens, 1, 6).is_none());
+    }
+
+    #[test]
+    fn token_at_counts_unicode_characters() {
+        let tokens = LexerBuilder::new()
+            .identifier_chars(IdentClass::Unicode)
+            .build("\u{03b1}\u{03b2} +".as_bytes())
+            .execute()
+            .unwrap();
+        assert!(matches!(token_at(&tokens, 1, 2).unwrap().value, TokenValue::Identifier(_)));
+        assert_eq!(token_at(&tokens, 1, 3).unwrap().value, TokenValue::Whitespace);
+        assert_eq!(token_at(&tokens, 1, 4).unwrap().value, TokenValue::Cross);
+    }
+}
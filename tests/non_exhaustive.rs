@@ -0,0 +1,33 @@
+// Matches from outside the crate, where `#[non_exhaustive]` applies and a
+// wildcard arm is required for these matches to compile.
+use arith_repl_v2::lexer::*;
+
+fn token_name(value: &TokenValue) -> &'static str {
+    match value {
+        TokenValue::Number(_) => "number",
+        TokenValue::Cross => "plus",
+        _ => "other",
+    }
+}
+
+fn error_name(value: &LexerErrorValue) -> &'static str {
+    match value {
+        LexerErrorValue::UnrecognizedToken => "unrecognized",
+        _ => "other",
+    }
+}
+
+#[test]
+fn token_value_wildcard_match() {
+    let tokens = Lexer::from_cstream(b"1 + 2").execute().unwrap();
+    let names: Vec<&str> = tokens.iter().map(|tok| token_name(tok.value())).collect();
+    assert_eq!(names, vec!["number", "other", "plus", "other", "number"]);
+}
+
+#[test]
+fn lexer_error_value_wildcard_match() {
+    let err = Lexer::from_cstream(b"$").execute().unwrap_err();
+    assert_eq!(error_name(err.value()), "unrecognized");
+    let err = Lexer::from_cstream(b"\\").execute().unwrap_err();
+    assert_eq!(error_name(err.value()), "other");
+}